@@ -0,0 +1,317 @@
+//ADMIN COMMANDS
+use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequest;
+use common::{
+    bans, bracket, config, connection_operations, crowd, error::Error, leaderboard, models,
+    permissions, pong, que, quiz, roles, send, snapshots, submissions, tournament,
+};
+use lambda::{lambda, Context};
+use serde::{Deserialize, Serialize};
+use simple_logger::SimpleLogger;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MatchResultMessage {
+    winner_slot: i64,
+    loser_slot: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TournamentMessage {
+    format: bracket::Format,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CrowdMessage {
+    enabled: bool,
+    options: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ModerationMessage {
+    submission: String,
+    approve: bool,
+    reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct QuizMessage {
+    format: String,
+    questions: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RenderMessage {
+    target: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BanMessage {
+    connection_id: Option<String>,
+    ip: Option<String>,
+    device: Option<String>,
+    player: Option<String>,
+    duration: Option<u64>,
+    reason: Option<String>,
+}
+
+#[lambda]
+#[tokio::main]
+async fn main(e: ApiGatewayWebsocketProxyRequest, _: Context) -> Result<(), Error> {
+    SimpleLogger::new().init().unwrap();
+    roles::init()?;
+
+    let connection_id = e
+        .clone()
+        .request_context
+        .connection_id
+        .ok_or("Missing Connection ID")?;
+    let unresolved_connection = models::UnresolvedConnection { id: connection_id };
+    let admin = connection_operations::find_connection_in_db(unresolved_connection).await?;
+
+    let route = e.request_context.route_key.clone().unwrap_or_default();
+    if !permissions::check(e.request_context.clone(), &admin, &route, None).await {
+        return Ok(());
+    }
+
+    match route.as_ref() {
+        "snapshot" => snapshot(e, admin).await,
+        "ban" => ban(e, admin).await,
+        "unban" => unban(e, admin).await,
+        "bans" => list_bans(e, admin).await,
+        "que" => que(e, admin).await,
+        "match_result" => match_result(e, admin).await,
+        "tournament_start" => tournament_start(e, admin).await,
+        "tournament_cancel" => tournament_cancel(e, admin).await,
+        "bracket" => show_bracket(e, admin).await,
+        "crowd" => crowd_mode(e, admin).await,
+        "submissions" => list_submissions(e, admin).await,
+        "moderate" => moderate(e, admin).await,
+        "render" => render(e, admin).await,
+        "quiz_load" => quiz_load(e, admin).await,
+        "quiz_next" => quiz_admin(e, admin, false).await,
+        "quiz_stop" => quiz_admin(e, admin, true).await,
+        _ => Ok(()),
+    }
+}
+
+async fn snapshot(
+    e: ApiGatewayWebsocketProxyRequest,
+    admin: models::Connection,
+) -> Result<(), Error> {
+    let connections =
+        connection_operations::find_audience(&admin, models::Audience::Everyone).await?;
+    send::snapshot(e.request_context, admin.id, &connections).await;
+    Ok(())
+}
+
+async fn que(e: ApiGatewayWebsocketProxyRequest, admin: models::Connection) -> Result<(), Error> {
+    let player_role = admin
+        .role
+        .and_then(models::Role::player_role)
+        .ok_or("Not an admin")?;
+    let queued = connection_operations::find_que(player_role).await?;
+    send::que(e.request_context, admin.id, &queued).await;
+    Ok(())
+}
+
+async fn match_result(
+    e: ApiGatewayWebsocketProxyRequest,
+    admin: models::Connection,
+) -> Result<(), Error> {
+    let message = e.body.clone().unwrap();
+    let message_content: MatchResultMessage = serde_json::from_str(&message)?;
+    pong::record_result(
+        e.request_context.clone(),
+        &admin,
+        message_content.winner_slot,
+        message_content.loser_slot,
+    )
+    .await?;
+
+    let leaderboard = leaderboard::top(config::leaderboard_size()).await?;
+    send::leaderboard(e.request_context, admin.id, &leaderboard).await;
+    Ok(())
+}
+
+async fn tournament_start(
+    e: ApiGatewayWebsocketProxyRequest,
+    admin: models::Connection,
+) -> Result<(), Error> {
+    let message = e.body.clone().unwrap();
+    let message_content: TournamentMessage = serde_json::from_str(&message)?;
+    tournament::start(e.request_context, &admin, message_content.format).await
+}
+
+async fn tournament_cancel(
+    e: ApiGatewayWebsocketProxyRequest,
+    admin: models::Connection,
+) -> Result<(), Error> {
+    tournament::cancel(e.request_context.clone(), &admin).await?;
+    que::fill_table(e.request_context, &admin).await
+}
+
+async fn show_bracket(
+    e: ApiGatewayWebsocketProxyRequest,
+    admin: models::Connection,
+) -> Result<(), Error> {
+    if let Some(bracket) = tournament::find(&admin.id).await? {
+        send::bracket(e.request_context, admin.id, &bracket).await;
+    }
+    Ok(())
+}
+
+async fn crowd_mode(
+    e: ApiGatewayWebsocketProxyRequest,
+    admin: models::Connection,
+) -> Result<(), Error> {
+    let message = e.body.clone().unwrap();
+    let message_content: CrowdMessage = serde_json::from_str(&message)?;
+    if message_content.enabled {
+        crowd::open(e.request_context, &admin, message_content.options).await
+    } else {
+        crowd::close(e.request_context, &admin).await
+    }
+}
+
+async fn list_submissions(
+    e: ApiGatewayWebsocketProxyRequest,
+    admin: models::Connection,
+) -> Result<(), Error> {
+    let pending = submissions::pending(&admin).await?;
+    send::submissions(e.request_context, admin.id, &pending).await;
+    Ok(())
+}
+
+async fn moderate(
+    e: ApiGatewayWebsocketProxyRequest,
+    admin: models::Connection,
+) -> Result<(), Error> {
+    let message = e.body.clone().unwrap();
+    let message_content: ModerationMessage = serde_json::from_str(&message)?;
+    submissions::moderate(
+        e.request_context,
+        &admin,
+        message_content.submission,
+        message_content.approve,
+        message_content.reason,
+    )
+    .await
+}
+
+async fn quiz_load(
+    e: ApiGatewayWebsocketProxyRequest,
+    admin: models::Connection,
+) -> Result<(), Error> {
+    let message = e.body.clone().unwrap();
+    let message_content: QuizMessage = serde_json::from_str(&message)?;
+    quiz::load(
+        e.request_context,
+        &admin,
+        &message_content.format,
+        &message_content.questions,
+    )
+    .await
+}
+
+async fn quiz_admin(
+    e: ApiGatewayWebsocketProxyRequest,
+    admin: models::Connection,
+    stop: bool,
+) -> Result<(), Error> {
+    if stop {
+        quiz::stop(e.request_context, &admin).await
+    } else {
+        quiz::next(e.request_context, &admin).await
+    }
+}
+
+async fn render(
+    e: ApiGatewayWebsocketProxyRequest,
+    admin: models::Connection,
+) -> Result<(), Error> {
+    let message = e.body.clone().unwrap();
+    let message_content: RenderMessage = serde_json::from_str(&message)?;
+    if let Some(image) = snapshots::render_target(&message_content.target, &admin.id).await? {
+        send::image(e.request_context, admin.id, &message_content.target, &image).await;
+    }
+    Ok(())
+}
+
+async fn ban(e: ApiGatewayWebsocketProxyRequest, admin: models::Connection) -> Result<(), Error> {
+    let message = e.body.clone().unwrap();
+    let mut message_content: BanMessage = serde_json::from_str(&message)?;
+    message_content.ip = match message_content.ip {
+        Some(ip) => Some(bans::ip_value(&ip)?),
+        None => None,
+    };
+
+    if let Some(connection_id) = message_content.connection_id.clone() {
+        let unresolved_connection = models::UnresolvedConnection { id: connection_id };
+        let connection =
+            connection_operations::find_connection_in_db(unresolved_connection).await?;
+        message_content.ip = message_content.ip.or(connection.source_ip);
+        message_content.device = message_content.device.or(connection.device);
+        message_content.player = message_content.player.or(connection.player_id);
+    }
+
+    if let Some(ip) = message_content.ip {
+        bans::ban(
+            "ip",
+            ip,
+            message_content.duration,
+            message_content.reason.clone(),
+        )
+        .await?;
+    }
+    if let Some(device) = message_content.device {
+        bans::ban(
+            "device",
+            device,
+            message_content.duration,
+            message_content.reason.clone(),
+        )
+        .await?;
+    }
+    if let Some(player) = message_content.player {
+        bans::ban(
+            "player",
+            player,
+            message_content.duration,
+            message_content.reason,
+        )
+        .await?;
+    }
+    if let Some(connection_id) = message_content.connection_id {
+        send::disconnect(e.request_context.clone(), connection_id).await;
+    }
+
+    list_bans(e, admin).await
+}
+
+async fn unban(e: ApiGatewayWebsocketProxyRequest, admin: models::Connection) -> Result<(), Error> {
+    let message = e.body.clone().unwrap();
+    let message_content: BanMessage = serde_json::from_str(&message)?;
+
+    if let Some(ip) = message_content.ip {
+        bans::unban("ip", &ip).await?;
+        if let Ok(value) = bans::ip_value(&ip) {
+            bans::unban("ip", &value).await?;
+        }
+    }
+    if let Some(device) = message_content.device {
+        bans::unban("device", &device).await?;
+    }
+    if let Some(player) = message_content.player {
+        bans::unban("player", &player).await?;
+    }
+
+    list_bans(e, admin).await
+}
+
+async fn list_bans(
+    e: ApiGatewayWebsocketProxyRequest,
+    admin: models::Connection,
+) -> Result<(), Error> {
+    let bans = bans::list().await?;
+    send::bans(e.request_context, admin.id, &bans).await;
+    Ok(())
+}
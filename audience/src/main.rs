@@ -0,0 +1,142 @@
+//FROM ANY CONNECTION
+use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequest;
+use common::{
+    canvas, config, connection_operations, error::Error, leaderboard, models, permissions, poll,
+    quiz, rate_limit, reactions, roles, send, submissions,
+};
+use lambda::{lambda, Context};
+use serde::{Deserialize, Serialize};
+use simple_logger::SimpleLogger;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VoteMessage {
+    poll: String,
+    choice: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AnswerMessage {
+    question: i64,
+    choice: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ReactionMessage {
+    reaction: String,
+}
+
+#[lambda]
+#[tokio::main]
+async fn main(e: ApiGatewayWebsocketProxyRequest, _: Context) -> Result<(), Error> {
+    SimpleLogger::new().init().unwrap();
+    roles::init()?;
+
+    let connection_id = e
+        .clone()
+        .request_context
+        .connection_id
+        .ok_or("Missing Connection ID")?;
+    let unresolved_connection = models::UnresolvedConnection { id: connection_id };
+    let connection = connection_operations::find_connection_in_db(unresolved_connection).await?;
+
+    let route = e.request_context.route_key.clone().unwrap_or_default();
+    if !permissions::check(e.request_context.clone(), &connection, &route, None).await {
+        return Ok(());
+    }
+
+    match route.as_ref() {
+        "leaderboard" => show_leaderboard(e, connection).await,
+        "vote" => vote(e, connection).await,
+        "polls" => show_polls(e, connection).await,
+        "reaction" => react(e, connection).await,
+        "submit" => submit(e, connection).await,
+        "pixel" => place_pixel(e, connection).await,
+        "answer" => answer(e, connection).await,
+        "canvas" => canvas::send_snapshot(e.request_context, connection.id).await,
+        _ => Ok(()),
+    }
+}
+
+async fn show_leaderboard(
+    e: ApiGatewayWebsocketProxyRequest,
+    connection: models::Connection,
+) -> Result<(), Error> {
+    let leaderboard = leaderboard::top(config::leaderboard_size()).await?;
+    send::leaderboard(e.request_context, connection.id, &leaderboard).await;
+    Ok(())
+}
+
+async fn vote(
+    e: ApiGatewayWebsocketProxyRequest,
+    connection: models::Connection,
+) -> Result<(), Error> {
+    let message = e.body.clone().unwrap();
+    let message_content: VoteMessage = serde_json::from_str(&message)?;
+    poll::vote(
+        e.request_context,
+        &connection,
+        message_content.poll,
+        message_content.choice,
+    )
+    .await
+}
+
+async fn answer(
+    e: ApiGatewayWebsocketProxyRequest,
+    connection: models::Connection,
+) -> Result<(), Error> {
+    let message = e.body.clone().unwrap();
+    let message_content: AnswerMessage = serde_json::from_str(&message)?;
+    quiz::answer(
+        e.request_context,
+        &connection,
+        message_content.question,
+        message_content.choice,
+    )
+    .await
+}
+
+async fn show_polls(
+    e: ApiGatewayWebsocketProxyRequest,
+    connection: models::Connection,
+) -> Result<(), Error> {
+    let polls = poll::results().await?;
+    send::polls(e.request_context, connection.id, &polls).await;
+    Ok(())
+}
+
+async fn react(
+    e: ApiGatewayWebsocketProxyRequest,
+    connection: models::Connection,
+) -> Result<(), Error> {
+    let message = e.body.clone().unwrap();
+    if !rate_limit::check(e.request_context.clone(), &connection, message.len()).await? {
+        return Ok(());
+    }
+    let message_content: ReactionMessage = serde_json::from_str(&message)?;
+    reactions::react(e.request_context, &connection, message_content.reaction).await
+}
+
+async fn submit(
+    e: ApiGatewayWebsocketProxyRequest,
+    connection: models::Connection,
+) -> Result<(), Error> {
+    let message = e.body.clone().unwrap();
+    if !rate_limit::check(e.request_context.clone(), &connection, message.len()).await? {
+        return Ok(());
+    }
+    let content: submissions::SubmissionContent = serde_json::from_str(&message)?;
+    submissions::submit(e.request_context, &connection, content).await
+}
+
+async fn place_pixel(
+    e: ApiGatewayWebsocketProxyRequest,
+    connection: models::Connection,
+) -> Result<(), Error> {
+    let message = e.body.clone().unwrap();
+    if !rate_limit::check(e.request_context.clone(), &connection, message.len()).await? {
+        return Ok(());
+    }
+    let pixel: canvas::PixelMessage = serde_json::from_str(&message)?;
+    canvas::place(e.request_context, &connection, pixel).await
+}
@@ -0,0 +1,208 @@
+use crate::error::Error;
+use crate::models::{MemberSet, Tally, TallyWindow};
+use crate::store;
+use crate::time;
+use dynomite::attr_map;
+use std::collections::{HashMap, HashSet};
+
+const FLUSH_GRACE: u64 = 100;
+const TALLY_RETENTION: u64 = 60;
+
+pub struct Batch {
+    pub table_id: String,
+    pub window: u64,
+    pub counts: HashMap<String, i64>,
+}
+
+pub fn current_window(tick: u64) -> u64 {
+    time::now_millis() / tick.max(1)
+}
+
+fn tally_id(kind: &str, table_id: &str, window: u64, choice: &str) -> String {
+    format!("{}#{}#{}#{}", kind, table_id, window, choice)
+}
+
+fn window_id(kind: &str, table_id: &str, window: u64) -> String {
+    format!("{}_window#{}#{}", kind, table_id, window)
+}
+
+fn pending_id(kind: &str, table_id: &str) -> String {
+    format!("{}_pending#{}", kind, table_id)
+}
+
+fn index_id(kind: &str) -> String {
+    format!("{}_pending", kind)
+}
+
+pub async fn add(kind: &str, table_id: &str, window: u64, choice: String) -> Result<(), Error> {
+    let expires_at = time::now_seconds() + TALLY_RETENTION;
+    store::increment_record(
+        tally_id(kind, table_id, window, &choice),
+        "votes",
+        attr_map!(
+            "kind" => kind.to_string(),
+            "table_id" => table_id.to_string(),
+            "window" => window,
+            "choice" => choice.clone(),
+            "clearAt" => expires_at
+        ),
+    )
+    .await?;
+
+    store::add_to_set(
+        window_id(kind, table_id, window),
+        "choices",
+        choice,
+        attr_map!(
+            "kind" => format!("{}_window", kind),
+            "table_id" => table_id.to_string(),
+            "window" => window,
+            "clearAt" => expires_at
+        ),
+    )
+    .await?;
+
+    // A vote for a window that was already flushed puts it back, so it is sent again
+    // with the late vote counted instead of being lost.
+    store::add_to_set(
+        pending_id(kind, table_id),
+        "members",
+        window.to_string(),
+        attr_map!(
+            "kind" => index_id(kind),
+            "clearAt" => expires_at
+        ),
+    )
+    .await?;
+    store::add_to_set(
+        index_id(kind),
+        "members",
+        table_id.to_string(),
+        attr_map!("kind" => index_id(kind)),
+    )
+    .await
+}
+
+pub fn due_windows(pending: &HashSet<String>, tick: u64, now: u64) -> Vec<u64> {
+    let tick = tick.max(1);
+    let mut due: Vec<u64> = pending
+        .iter()
+        .filter_map(|window| window.parse().ok())
+        .filter(|window| (window + 1) * tick + FLUSH_GRACE <= now)
+        .collect();
+    due.sort_unstable();
+    due
+}
+
+// Returns the table's ended windows that this caller claimed, and whether any windows
+// are still open.
+pub async fn flush_table(
+    kind: &str,
+    table_id: &str,
+    tick: u64,
+) -> Result<(Vec<Batch>, bool), Error> {
+    let pending: Option<MemberSet> = store::get_record(pending_id(kind, table_id)).await?;
+    let pending = pending
+        .and_then(|pending| pending.members)
+        .unwrap_or_default();
+    let due = due_windows(&pending, tick, time::now_millis());
+    let open = pending.len() > due.len();
+
+    let mut batches = Vec::new();
+    for window in due {
+        let claimed =
+            store::take_from_set(pending_id(kind, table_id), "members", window.to_string()).await?;
+        if !claimed {
+            continue;
+        }
+        if let Some(counts) = collect(kind, table_id, window).await? {
+            batches.push(Batch {
+                table_id: table_id.to_string(),
+                window,
+                counts,
+            });
+        }
+    }
+    Ok((batches, open))
+}
+
+// Run from the scheduler, so the last window before a quiet period is still sent.
+pub async fn flush_due(kind: &str, tick: u64) -> Result<Vec<Batch>, Error> {
+    let index: Option<MemberSet> = store::get_record(index_id(kind)).await?;
+    let mut batches = Vec::new();
+    for table_id in index.and_then(|index| index.members).unwrap_or_default() {
+        // Removed before reading the table's windows, so a vote landing in between
+        // adds the table back.
+        store::remove_from_set(index_id(kind), "members", table_id.clone()).await?;
+        let (flushed, open) = flush_table(kind, &table_id, tick).await?;
+        batches.extend(flushed);
+        if open {
+            store::add_to_set(
+                index_id(kind),
+                "members",
+                table_id,
+                attr_map!("kind" => index_id(kind)),
+            )
+            .await?;
+        }
+    }
+    Ok(batches)
+}
+
+async fn collect(
+    kind: &str,
+    table_id: &str,
+    window: u64,
+) -> Result<Option<HashMap<String, i64>>, Error> {
+    let marker: Option<TallyWindow> = store::get_record(window_id(kind, table_id, window)).await?;
+    let choices = match marker.and_then(|marker| marker.choices) {
+        Some(choices) => choices,
+        None => return Ok(None),
+    };
+
+    let ids = choices
+        .iter()
+        .map(|choice| tally_id(kind, table_id, window, choice))
+        .collect();
+    Ok(Some(
+        store::get_records::<Tally>(ids)
+            .await?
+            .into_iter()
+            .map(|tally| (tally.choice, tally.votes))
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(windows: &[&str]) -> HashSet<String> {
+        windows.iter().map(|window| window.to_string()).collect()
+    }
+
+    #[test]
+    fn windows_are_due_after_the_grace_period() {
+        let windows = pending(&["10"]);
+        assert!(due_windows(&windows, 1000, 11_000).is_empty());
+        assert!(due_windows(&windows, 1000, 11_099).is_empty());
+        assert_eq!(due_windows(&windows, 1000, 11_100), vec![10]);
+    }
+
+    #[test]
+    fn open_window_stays_pending() {
+        let windows = pending(&["10", "11"]);
+        assert_eq!(due_windows(&windows, 1000, 11_500), vec![10]);
+    }
+
+    #[test]
+    fn late_windows_flush_oldest_first() {
+        let windows = pending(&["12", "7", "10", "not a window"]);
+        assert_eq!(due_windows(&windows, 500, 60_000), vec![7, 10, 12]);
+    }
+
+    #[test]
+    fn zero_tick_does_not_divide_by_zero() {
+        assert_eq!(due_windows(&pending(&["5"]), 0, 1_000), vec![5]);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::error::Error;
+use crate::metadata;
+use crate::models::Ban;
+use crate::store;
+use crate::time;
+
+const KIND: &str = "ban";
+
+fn ban_id(target: &str, value: &str) -> String {
+    format!("{}#{}#{}", KIND, target, value)
+}
+
+pub fn ip_value(ip: &str) -> Result<String, Error> {
+    metadata::source_ip(Some(ip.to_string()))
+        .ok_or_else(|| "IP bans are disabled while ipPrivacy is drop".into())
+}
+
+fn is_active(ban: &Ban, now: u64) -> bool {
+    ban.expires_at
+        .map(|expires_at| expires_at > now)
+        .unwrap_or(true)
+}
+
+fn candidates(
+    ip: &Option<String>,
+    device: &Option<String>,
+    player: &Option<String>,
+) -> Vec<String> {
+    vec![("ip", ip), ("device", device), ("player", player)]
+        .into_iter()
+        .filter_map(|(target, value)| value.as_ref().map(|value| ban_id(target, value)))
+        .collect()
+}
+
+pub async fn is_banned(
+    ip: &Option<String>,
+    device: &Option<String>,
+    player: &Option<String>,
+) -> Result<bool, Error> {
+    let now = time::now_seconds();
+    for id in candidates(ip, device, player) {
+        let ban: Option<Ban> = store::get_record(id).await?;
+        if ban.map_or(false, |ban| is_active(&ban, now)) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+pub async fn ban(
+    target: &str,
+    value: String,
+    duration: Option<u64>,
+    reason: Option<String>,
+) -> Result<Ban, Error> {
+    let ban = Ban {
+        id: ban_id(target, &value),
+        kind: KIND.to_string(),
+        target: target.to_string(),
+        value,
+        reason,
+        expires_at: duration.map(|duration| time::now_seconds() + duration),
+    };
+    store::put_record(ban.clone()).await?;
+    Ok(ban)
+}
+
+pub async fn unban(target: &str, value: &str) -> Result<(), Error> {
+    store::delete_record(ban_id(target, value)).await
+}
+
+pub async fn list() -> Result<Vec<Ban>, Error> {
+    let now = time::now_seconds();
+    let bans: Vec<Ban> = store::scan_records(KIND).await?;
+    Ok(bans.into_iter().filter(|ban| is_active(ban, now)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ban(expires_at: Option<u64>) -> Ban {
+        Ban {
+            expires_at,
+            ..Ban::default()
+        }
+    }
+
+    #[test]
+    fn bans_last_until_they_expire() {
+        assert!(is_active(&ban(None), 1_000));
+        assert!(is_active(&ban(Some(1_001)), 1_000));
+        assert!(!is_active(&ban(Some(1_000)), 1_000));
+        assert!(!is_active(&ban(Some(10)), 1_000));
+    }
+
+    #[test]
+    fn checks_every_identity_the_connection_presents() {
+        let some = |value: &str| Some(value.to_string());
+        assert_eq!(
+            candidates(&some("1.2.3.4"), &some("abc"), &some("p1")),
+            vec!["ban#ip#1.2.3.4", "ban#device#abc", "ban#player#p1"]
+        );
+        assert_eq!(
+            candidates(&None, &some("abc"), &None),
+            vec!["ban#device#abc"]
+        );
+        assert!(candidates(&None, &None, &None).is_empty());
+    }
+
+    #[test]
+    fn targets_do_not_collide() {
+        assert_ne!(ban_id("ip", "1"), ban_id("device", "1"));
+        assert_ne!(ban_id("device", "p1"), ban_id("player", "p1"));
+    }
+}
@@ -0,0 +1,377 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    SingleElimination,
+    RoundRobin,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Entrant {
+    pub id: String,
+    pub nickname: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct BracketMatch {
+    pub round: usize,
+    pub players: Vec<Option<String>>,
+    pub winner: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Bracket {
+    pub format: Format,
+    pub entrants: Vec<Entrant>,
+    pub matches: Vec<BracketMatch>,
+    pub current: Option<usize>,
+    pub withdrawn: Vec<String>,
+    pub champion: Option<String>,
+}
+
+impl Bracket {
+    pub fn new(format: Format, entrants: Vec<Entrant>) -> Result<Bracket, &'static str> {
+        if entrants.len() < 2 {
+            return Err("Not enough players");
+        }
+        let ids: Vec<String> = entrants.iter().map(|entrant| entrant.id.clone()).collect();
+        let matches = match format {
+            Format::SingleElimination => single_elimination(&ids),
+            Format::RoundRobin => round_robin(&ids),
+        };
+
+        let mut bracket = Bracket {
+            format,
+            entrants,
+            matches,
+            current: None,
+            withdrawn: Vec::new(),
+            champion: None,
+        };
+        bracket.advance();
+        Ok(bracket)
+    }
+
+    pub fn next_match(&self) -> Option<usize> {
+        self.matches.iter().position(|game| {
+            game.winner.is_none() && game.players.iter().all(|player| player.is_some())
+        })
+    }
+
+    pub fn record(&mut self, winner: &str, loser: &str) -> Result<(), &'static str> {
+        let index = self.current.ok_or("No match in progress")?;
+        let game = &mut self.matches[index];
+        let playing = |id: &str| {
+            game.players
+                .iter()
+                .any(|player| player.as_deref() == Some(id))
+        };
+        if winner == loser || !playing(winner) || !playing(loser) {
+            return Err("Players are not in the current match");
+        }
+        game.winner = Some(winner.to_string());
+        self.current = None;
+        self.advance();
+        Ok(())
+    }
+
+    pub fn withdraw(&mut self, id: &str) {
+        if !self.withdrawn.iter().any(|withdrawn| withdrawn == id) {
+            self.withdrawn.push(id.to_string());
+        }
+        self.advance();
+    }
+
+    pub fn opponent(&self, index: usize, id: &str) -> Option<String> {
+        self.matches[index]
+            .players
+            .iter()
+            .flatten()
+            .find(|player| player.as_str() != id)
+            .cloned()
+    }
+
+    pub fn is_eliminated(&self, id: &str) -> bool {
+        self.format == Format::SingleElimination
+            && self.matches.iter().any(|game| {
+                game.players
+                    .iter()
+                    .any(|player| player.as_deref() == Some(id))
+                    && game.winner.as_ref().map_or(false, |winner| winner != id)
+            })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.champion.is_some()
+    }
+
+    fn advance(&mut self) {
+        loop {
+            let mut changed = false;
+            for index in 0..self.matches.len() {
+                if self.matches[index].winner.is_none() {
+                    if let Some(winner) = self.walkover(index) {
+                        self.matches[index].winner = Some(winner);
+                        changed = true;
+                    }
+                }
+                if self.format == Format::SingleElimination {
+                    changed |= self.promote_winner(index);
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        if self.matches.iter().all(|game| game.winner.is_some()) {
+            self.champion = match self.format {
+                Format::SingleElimination => {
+                    self.matches.last().and_then(|game| game.winner.clone())
+                }
+                Format::RoundRobin => self.round_robin_leader(),
+            };
+        }
+    }
+
+    fn walkover(&self, index: usize) -> Option<String> {
+        let game = &self.matches[index];
+        let present: Vec<&String> = game.players.iter().flatten().collect();
+        let bye = game.round == 0 && present.len() == 1;
+        if bye {
+            return present.first().map(|player| player.to_string());
+        }
+        if present.len() < 2 {
+            return None;
+        }
+
+        let active: Vec<&&String> = present
+            .iter()
+            .filter(|player| !self.withdrawn.contains(player))
+            .collect();
+        match active.len() {
+            2 => None,
+            1 => active.first().map(|player| player.to_string()),
+            _ => present.first().map(|player| player.to_string()),
+        }
+    }
+
+    fn promote_winner(&mut self, index: usize) -> bool {
+        let winner = match &self.matches[index].winner {
+            Some(winner) => winner.clone(),
+            None => return false,
+        };
+        let round = self.matches[index].round;
+        let first = self
+            .matches
+            .iter()
+            .position(|game| game.round == round)
+            .unwrap_or(0);
+        let next_first = match self.matches.iter().position(|game| game.round == round + 1) {
+            Some(next_first) => next_first,
+            None => return false,
+        };
+
+        let offset = index - first;
+        let next = &mut self.matches[next_first + offset / 2];
+        let position = offset % 2;
+        if next.players[position].as_ref() == Some(&winner) {
+            return false;
+        }
+        next.players[position] = Some(winner);
+        true
+    }
+
+    fn round_robin_leader(&self) -> Option<String> {
+        let wins = |id: &str| {
+            self.matches
+                .iter()
+                .filter(|game| game.winner.as_deref() == Some(id))
+                .count()
+        };
+        self.entrants
+            .iter()
+            .filter(|entrant| !self.withdrawn.contains(&entrant.id))
+            .fold(None, |best: Option<(&Entrant, usize)>, entrant| {
+                let entrant_wins = wins(&entrant.id);
+                match best {
+                    Some((_, best_wins)) if best_wins >= entrant_wins => best,
+                    _ => Some((entrant, entrant_wins)),
+                }
+            })
+            .map(|(entrant, _)| entrant.id.clone())
+    }
+}
+
+fn single_elimination(ids: &[String]) -> Vec<BracketMatch> {
+    let size = ids.len().next_power_of_two();
+    let mut matches = Vec::new();
+
+    for seed in 0..size / 2 {
+        matches.push(BracketMatch {
+            round: 0,
+            players: vec![ids.get(seed).cloned(), ids.get(size - 1 - seed).cloned()],
+            winner: None,
+        });
+    }
+
+    let mut round = 1;
+    let mut games = size / 4;
+    while games > 0 {
+        for _ in 0..games {
+            matches.push(BracketMatch {
+                round,
+                players: vec![None, None],
+                winner: None,
+            });
+        }
+        round += 1;
+        games /= 2;
+    }
+    matches
+}
+
+fn round_robin(ids: &[String]) -> Vec<BracketMatch> {
+    let mut circle: Vec<Option<String>> = ids.iter().cloned().map(Some).collect();
+    if circle.len() % 2 == 1 {
+        circle.push(None);
+    }
+    let size = circle.len();
+    let mut matches = Vec::new();
+
+    for round in 0..size - 1 {
+        for index in 0..size / 2 {
+            if let (Some(home), Some(away)) = (&circle[index], &circle[size - 1 - index]) {
+                matches.push(BracketMatch {
+                    round,
+                    players: vec![Some(home.clone()), Some(away.clone())],
+                    winner: None,
+                });
+            }
+        }
+        let last = circle.pop().unwrap_or(None);
+        circle.insert(1, last);
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entrants(ids: &[&str]) -> Vec<Entrant> {
+        ids.iter()
+            .map(|id| Entrant {
+                id: id.to_string(),
+                nickname: None,
+            })
+            .collect()
+    }
+
+    fn play(bracket: &mut Bracket, winner: &str, loser: &str) {
+        bracket.current = bracket.next_match();
+        bracket.record(winner, loser).unwrap();
+    }
+
+    #[test]
+    fn needs_two_entrants() {
+        assert!(Bracket::new(Format::SingleElimination, entrants(&["a"])).is_err());
+        assert!(Bracket::new(Format::RoundRobin, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn single_elimination_gives_top_seed_a_bye() {
+        let bracket = Bracket::new(Format::SingleElimination, entrants(&["a", "b", "c"])).unwrap();
+
+        assert_eq!(bracket.matches.len(), 3);
+        assert_eq!(bracket.matches[0].winner.as_deref(), Some("a"));
+        assert_eq!(bracket.matches[2].players[0].as_deref(), Some("a"));
+        assert_eq!(bracket.next_match(), Some(1));
+    }
+
+    #[test]
+    fn single_elimination_crowns_the_final_winner() {
+        let mut bracket =
+            Bracket::new(Format::SingleElimination, entrants(&["a", "b", "c", "d"])).unwrap();
+
+        play(&mut bracket, "a", "d");
+        play(&mut bracket, "c", "b");
+        assert!(bracket.is_eliminated("d"));
+        assert!(!bracket.is_finished());
+        assert_eq!(
+            bracket.matches[2].players,
+            vec![Some("a".to_string()), Some("c".to_string())]
+        );
+
+        play(&mut bracket, "c", "a");
+        assert_eq!(bracket.champion.as_deref(), Some("c"));
+        assert_eq!(bracket.next_match(), None);
+    }
+
+    #[test]
+    fn record_rejects_players_outside_the_current_match() {
+        let mut bracket =
+            Bracket::new(Format::SingleElimination, entrants(&["a", "b", "c", "d"])).unwrap();
+
+        assert!(bracket.record("a", "d").is_err());
+        bracket.current = bracket.next_match();
+        assert!(bracket.record("a", "b").is_err());
+        assert!(bracket.record("a", "a").is_err());
+        assert!(bracket.record("a", "d").is_ok());
+    }
+
+    #[test]
+    fn withdrawn_player_gives_a_walkover() {
+        let mut bracket = Bracket::new(Format::SingleElimination, entrants(&["a", "b"])).unwrap();
+
+        bracket.withdraw("b");
+        assert_eq!(bracket.champion.as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn round_robin_pairs_everyone_once() {
+        let bracket = Bracket::new(Format::RoundRobin, entrants(&["a", "b", "c"])).unwrap();
+
+        let mut pairs: Vec<Vec<String>> = bracket
+            .matches
+            .iter()
+            .map(|game| {
+                let mut pair: Vec<String> = game.players.iter().flatten().cloned().collect();
+                pair.sort();
+                pair
+            })
+            .collect();
+        pairs.sort();
+        assert_eq!(
+            pairs,
+            vec![
+                vec!["a".to_string(), "b".to_string()],
+                vec!["a".to_string(), "c".to_string()],
+                vec!["b".to_string(), "c".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn round_robin_champion_has_most_wins() {
+        let mut bracket = Bracket::new(Format::RoundRobin, entrants(&["a", "b", "c"])).unwrap();
+
+        while let Some(index) = bracket.next_match() {
+            let players: Vec<String> = bracket.matches[index]
+                .players
+                .iter()
+                .flatten()
+                .cloned()
+                .collect();
+            let (winner, loser) = if players[1] == "b" {
+                (&players[1], &players[0])
+            } else {
+                (&players[0], &players[1])
+            };
+            bracket.current = Some(index);
+            bracket.record(winner, loser).unwrap();
+        }
+        assert_eq!(bracket.champion.as_deref(), Some("b"));
+    }
+}
@@ -0,0 +1,128 @@
+use crate::config;
+use crate::connection_operations::*;
+use crate::cooldown;
+use crate::error::Error;
+use crate::models::*;
+use crate::send;
+use crate::store;
+use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequestContext;
+use dynomite::attr_map;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+const KIND: &str = "canvas";
+const COOLDOWN_SCOPE: &str = "canvas";
+const CHUNK_PIXELS: usize = 4096;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PixelMessage {
+    pub x: u32,
+    pub y: u32,
+    pub color: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    pub palette: Vec<String>,
+    pub pixels: Vec<u8>,
+}
+
+impl Canvas {
+    pub fn blank() -> Canvas {
+        let width = config::canvas_width();
+        let height = config::canvas_height();
+        Canvas {
+            width,
+            height,
+            palette: config::canvas_palette(),
+            pixels: vec![0; (width * height) as usize],
+        }
+    }
+
+    pub fn contains(&self, x: u32, y: u32, color: u8) -> bool {
+        x < self.width && y < self.height && (color as usize) < self.palette.len()
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, color: u8) {
+        if self.contains(x, y, color) {
+            self.pixels[(y * self.width + x) as usize] = color;
+        }
+    }
+
+    fn chunks(&self) -> usize {
+        (self.pixels.len() + CHUNK_PIXELS - 1) / CHUNK_PIXELS
+    }
+
+    pub fn compress(&self) -> Result<String, Error> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&self.pixels)?;
+        Ok(base64::encode(encoder.finish()?))
+    }
+}
+
+fn chunk_id(chunk: usize) -> String {
+    format!("{}#{}", KIND, chunk)
+}
+
+pub async fn load() -> Result<Canvas, Error> {
+    let mut canvas = Canvas::blank();
+    let ids = (0..canvas.chunks()).map(chunk_id).collect();
+    for chunk in store::get_records::<CanvasChunk>(ids).await? {
+        for (index, color) in chunk.pixels {
+            let index = index as u32;
+            canvas.set(index % canvas.width, index / canvas.width, color);
+        }
+    }
+    Ok(canvas)
+}
+
+pub async fn place(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection: &Connection,
+    pixel: PixelMessage,
+) -> Result<(), Error> {
+    if !Canvas::blank().contains(pixel.x, pixel.y, pixel.color) {
+        send::canvas_rejected(request_context, "invalid_pixel").await;
+        return Ok(());
+    }
+    if let Some(remaining) =
+        cooldown::claim_scope(connection, COOLDOWN_SCOPE, config::canvas_cooldown()).await?
+    {
+        send::canvas_cooldown(request_context, remaining).await;
+        return Ok(());
+    }
+
+    let index = (pixel.y * config::canvas_width() + pixel.x) as usize;
+    store::update_record(
+        chunk_id(index / CHUNK_PIXELS),
+        attr_map!(
+            "kind" => KIND.to_string(),
+            format!("{}{}", PIXEL_PREFIX, index) => pixel.color
+        ),
+    )
+    .await?;
+
+    for display in find_admins(Role::PlayerDisplay).await? {
+        send::canvas_diff(
+            request_context.clone(),
+            display.id,
+            &[[pixel.x, pixel.y, pixel.color as u32]],
+        )
+        .await;
+    }
+    Ok(())
+}
+
+pub async fn send_snapshot(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection_id: String,
+) -> Result<(), Error> {
+    let canvas = load().await?;
+    let data = canvas.compress()?;
+    send::canvas(request_context, connection_id, &canvas, data).await;
+    Ok(())
+}
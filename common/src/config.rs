@@ -0,0 +1,221 @@
+use crate::join::QrFormat;
+use crate::models::Role;
+use crate::rate_limit::RateLimit;
+use std::collections::HashMap;
+use std::env;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IpPrivacy {
+    Keep,
+    Hash,
+    Drop,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rotation {
+    WinnerStays,
+    BothRotate,
+    BestOf(i64),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Matchmaking {
+    Arrival,
+    Rating,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotStorage {
+    Local(String),
+    S3 { bucket: String, prefix: String },
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+pub fn capacity(game: &str, default: i64) -> i64 {
+    env_or(&format!("{}Capacity", game), default)
+}
+
+pub fn ready_timeout() -> u64 {
+    env_or("readyTimeout", 15)
+}
+
+pub fn scheduler_tick() -> u64 {
+    env_or("schedulerTick", 1000)
+}
+
+pub fn rate_limit(role: Role) -> RateLimit {
+    let limits: HashMap<String, RateLimit> = env::var("rateLimits")
+        .ok()
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .unwrap_or_default();
+
+    limits
+        .get(role.name())
+        .or_else(|| limits.get("default"))
+        .copied()
+        .unwrap_or_default()
+}
+
+pub fn cooldown(role: Role) -> u64 {
+    let cooldowns: HashMap<String, u64> = env::var("cooldowns")
+        .ok()
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .unwrap_or_default();
+
+    cooldowns.get(role.name()).copied().unwrap_or(0)
+}
+
+pub fn crowd_tick() -> u64 {
+    env_or("crowdTick", 1000)
+}
+
+pub fn crowd_vote_limit() -> i64 {
+    env_or("crowdVoteLimit", 1)
+}
+
+pub fn reactions() -> Vec<String> {
+    env::var("reactions")
+        .unwrap_or_else(|_| "like,love,laugh,wow,clap".to_string())
+        .split(',')
+        .map(|reaction| reaction.trim().to_string())
+        .filter(|reaction| !reaction.is_empty())
+        .collect()
+}
+
+pub fn reaction_window() -> u64 {
+    env_or("reactionWindow", 500)
+}
+
+pub fn submission_text_limit() -> usize {
+    env_or("submissionTextLimit", 140)
+}
+
+pub fn submission_stroke_points() -> usize {
+    env_or("submissionStrokePoints", 2000)
+}
+
+pub fn submission_image_bytes() -> usize {
+    env_or("submissionImageBytes", 32768)
+}
+
+pub fn submission_image_size() -> u32 {
+    env_or("submissionImageSize", 256)
+}
+
+pub fn submission_hold() -> u64 {
+    env_or("submissionHoldSeconds", 300)
+}
+
+pub fn canvas_width() -> u32 {
+    env_or("canvasWidth", 64)
+}
+
+pub fn canvas_height() -> u32 {
+    env_or("canvasHeight", 64)
+}
+
+pub fn canvas_palette() -> Vec<String> {
+    env::var("canvasPalette")
+        .unwrap_or_else(|_| {
+            "#ffffff,#e4e4e4,#888888,#222222,#ffa7d1,#e50000,#e59500,#a06a42,\
+             #e5d900,#94e044,#02be01,#00d3dd,#0083c7,#0000ea,#cf6ee4,#820080"
+                .to_string()
+        })
+        .split(',')
+        .map(|color| color.trim().to_string())
+        .filter(|color| !color.is_empty())
+        .collect()
+}
+
+pub fn canvas_cooldown() -> u64 {
+    env_or("canvasCooldown", 30)
+}
+
+pub fn quiz_duration() -> u64 {
+    env_or("quizDuration", 20)
+}
+
+pub fn quiz_points() -> i64 {
+    env_or("quizPoints", 1000)
+}
+
+// Local storage is meant for running the handlers outside Lambda, where /tmp is not
+// thrown away with the container.
+pub fn snapshot_storage() -> SnapshotStorage {
+    let bucket = env::var("snapshotBucket").unwrap_or_default();
+    match env::var("snapshotStorage").as_deref() {
+        Ok("s3") if !bucket.is_empty() => SnapshotStorage::S3 {
+            bucket,
+            prefix: env::var("snapshotPrefix").unwrap_or_default(),
+        },
+        _ => SnapshotStorage::Local(env_or("snapshotDir", "/tmp/snapshots".to_string())),
+    }
+}
+
+pub fn snapshot_interval() -> u64 {
+    env_or("snapshotInterval", 15)
+}
+
+pub fn snapshot_scale() -> usize {
+    env_or("snapshotScale", 8)
+}
+
+pub fn qr_format() -> QrFormat {
+    match env::var("qrFormat").as_deref() {
+        Ok("png") => QrFormat::Png,
+        _ => QrFormat::Svg,
+    }
+}
+
+pub fn ip_privacy() -> IpPrivacy {
+    match env::var("ipPrivacy").as_deref() {
+        Ok("hash") => IpPrivacy::Hash,
+        Ok("drop") => IpPrivacy::Drop,
+        _ => IpPrivacy::Keep,
+    }
+}
+
+pub fn max_connections_per_ip() -> i64 {
+    env_or("maxConnectionsPerIp", 0)
+}
+
+pub fn pong_rotation() -> Rotation {
+    let rotation = env::var("pongRotation").unwrap_or_default();
+    match rotation.as_str() {
+        "both_rotate" => Rotation::BothRotate,
+        _ => match rotation.strip_prefix("best_of:").map(str::parse) {
+            Some(Ok(games)) if games > 0 => Rotation::BestOf(games),
+            _ => Rotation::WinnerStays,
+        },
+    }
+}
+
+pub fn leaderboard_size() -> usize {
+    env_or("leaderboardSize", 10)
+}
+
+pub fn initial_rating() -> f64 {
+    env_or("initialRating", 1200.0)
+}
+
+pub fn rating_k() -> f64 {
+    env_or("ratingK", 32.0)
+}
+
+pub fn pong_matchmaking() -> Matchmaking {
+    match env::var("pongMatchmaking").as_deref() {
+        Ok("rating") => Matchmaking::Rating,
+        _ => Matchmaking::Arrival,
+    }
+}
+
+pub fn matchmaking_max_wait() -> u64 {
+    env_or("matchmakingMaxWait", 120)
+}
@@ -1,14 +1,18 @@
 use super::error::Error;
+use super::game;
+use super::join;
 use super::models::*;
+use super::time;
 use dynomite::{
     attr_map,
     dynamodb::{
         DeleteItemInput, DynamoDb, DynamoDbClient, GetItemInput, PutItemInput, ScanInput,
-        UpdateItemInput,
+        UpdateItemError, UpdateItemInput,
     },
-    FromAttributes, Item,
+    Attribute, FromAttributes, Item,
 };
 use log::debug;
+use rusoto_core::RusotoError;
 use std::collections::HashMap;
 use std::env;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
@@ -36,110 +40,505 @@ pub async fn find_connection_in_db(connection: UnresolvedConnection) -> Result<C
 }
 
 pub async fn find_admin(role: Role) -> Result<Connection, Error> {
-    let client = DynamoDbClient::new(Default::default());
-    let admin_role = match role {
-        Role::PlayerPong => Role::AdminPong,
-        Role::PlayerDisplay => Role::AdminDisplay,
-        _ => return Err("Unknown player".into()),
-    };
+    find_admins(role)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "No admin found".into())
+}
+
+pub async fn find_admins(role: Role) -> Result<Vec<Connection>, Error> {
+    let admin_role = role.admin_role().ok_or("Unknown player")?;
+    let mut admins = scan_connections(role_filter("#R = :val", admin_role, None)).await?;
+    admins.sort_by_key(|admin| admin.connected_at.unwrap_or(0));
+    Ok(admins)
+}
+
+pub async fn find_table_admin(player: &Connection) -> Result<Connection, Error> {
+    match &player.table_id {
+        Some(table_id) => {
+            find_connection_in_db(UnresolvedConnection {
+                id: table_id.clone(),
+            })
+            .await
+        }
+        None => find_admin(player.role.ok_or("Unknown player")?).await,
+    }
+}
+
+pub async fn find_players(admin: &Connection) -> Result<Vec<Connection>, Error> {
+    let player_role = admin
+        .role
+        .and_then(|role| role.player_role())
+        .ok_or("Unknown player")?;
 
     let mut expression_attribute_names = HashMap::new();
     expression_attribute_names.insert("#R".to_string(), "role".to_string());
+
+    scan_connections(ScanInput {
+        expression_attribute_names: Some(expression_attribute_names),
+        expression_attribute_values: Some(attr_map!(
+            ":val" => player_role,
+            ":table" => admin.id.clone()
+        )),
+        filter_expression: Some("#R = :val and table_id = :table".into()),
+        ..ScanInput::default()
+    })
+    .await
+}
+
+pub async fn find_room_admin(room: String) -> Result<Connection, Error> {
+    let mut expression_attribute_names = HashMap::new();
+    expression_attribute_names.insert("#R".to_string(), "role".to_string());
+    expression_attribute_names.insert("#O".to_string(), "room".to_string());
+
+    let mut expression_attribute_values = attr_map!(":room" => room);
+    let mut admin_roles = Vec::new();
+    for (index, mode) in game::modes().iter().enumerate() {
+        expression_attribute_values
+            .insert(format!(":admin{}", index), mode.admin_role().into_attr());
+        admin_roles.push(format!("#R = :admin{}", index));
+    }
+
+    let admins = scan_connections(ScanInput {
+        expression_attribute_names: Some(expression_attribute_names),
+        expression_attribute_values: Some(expression_attribute_values),
+        filter_expression: Some(format!("#O = :room and ({})", admin_roles.join(" or "))),
+        ..ScanInput::default()
+    })
+    .await?;
+
+    admins
+        .into_iter()
+        .next()
+        .ok_or_else(|| "Unknown room".into())
+}
+
+pub async fn find_display_admin(connection: &Connection) -> Result<Connection, Error> {
+    if let Some(room) = &connection.room {
+        if let Ok(admin) = find_room_admin(room.clone()).await {
+            if admin.role == Some(Role::AdminDisplay) {
+                return Ok(admin);
+            }
+        }
+    }
+    find_admin(Role::PlayerDisplay).await
+}
+
+pub async fn allocate_room(id: String) -> Result<String, Error> {
+    let client = DynamoDbClient::new(Default::default());
+    let unresolved_connection = UnresolvedConnection { id };
+
+    for _ in 0..5 {
+        let room = join::generate_code();
+        if find_room_admin(room.clone()).await.is_ok() {
+            continue;
+        }
+
+        let mut expression_attribute_names = HashMap::new();
+        expression_attribute_names.insert("#O".to_string(), "room".to_string());
+        client
+            .update_item(UpdateItemInput {
+                table_name: get_connections_table(),
+                key: unresolved_connection.key(),
+                update_expression: Some("SET #O = :room".to_string()),
+                expression_attribute_names: Some(expression_attribute_names),
+                expression_attribute_values: Some(attr_map!(
+                    ":room" => room.clone()
+                )),
+                ..UpdateItemInput::default()
+            })
+            .await?;
+        return Ok(room);
+    }
+
+    Err("Can't allocate room".into())
+}
+
+pub async fn find_next_in_que(role: Role) -> Result<Connection, Error> {
+    let queued = scan_connections(role_filter(
+        "#R = :val and #Q = :que and attribute_not_exists(ready_by) \
+         and attribute_not_exists(tournament_id)",
+        role,
+        Some(true),
+    ))
+    .await?;
+
+    queued
+        .into_iter()
+        .min_by_key(|player| player.queued_at.unwrap_or(0))
+        .ok_or_else(|| "No next player found".into())
+}
+
+pub async fn find_que(role: Role) -> Result<Vec<Connection>, Error> {
+    let mut queued = find_queued(role).await?;
+    queued.sort_by_key(|player| player.queued_at.unwrap_or(0));
+    Ok(queued)
+}
+
+pub async fn mark_player_active(id: String) {
+    let client = DynamoDbClient::new(Default::default());
+    let unresolved_connection = UnresolvedConnection { id };
+
     let res = client
-        .scan(ScanInput {
+        .update_item(UpdateItemInput {
             table_name: get_connections_table(),
-            expression_attribute_names: Some(expression_attribute_names),
+            update_expression: Some(
+                "SET que = :que REMOVE ready_by, clearAt, series_wins".to_string(),
+            ),
             expression_attribute_values: Some(attr_map!(
-            ":val" =>  admin_role
+                ":que" => false
             )),
-            filter_expression: Some("#R = :val".into()),
-            ..ScanInput::default()
+            key: unresolved_connection.key(),
+            ..UpdateItemInput::default()
         })
-        .await?;
-
-    let items = res.items.ok_or("No admin found")?;
-    let item = items.first().ok_or("No admin found")?.clone();
-    let admin_item = Connection::from_attrs(item);
+        .await;
 
-    admin_item.map_err(|e| e.into())
+    if let Err(err) = res {
+        debug!("error setting que {}", err);
+    }
 }
 
-pub async fn find_players(role: Role) -> Result<Vec<Connection>, Error> {
+pub async fn await_ready(id: String, seat: &Seat, ready_by: u64) -> Result<bool, Error> {
     let client = DynamoDbClient::new(Default::default());
-    let player_role = match role {
-        Role::AdminPong => Role::PlayerPong,
-        Role::AdminDisplay => Role::PlayerDisplay,
-        _ => return Err("Unknown player".into()),
-    };
+    let unresolved_connection = UnresolvedConnection { id };
 
     let mut expression_attribute_names = HashMap::new();
-    expression_attribute_names.insert("#R".to_string(), "role".to_string());
+    expression_attribute_names.insert("#Q".to_string(), "que".to_string());
 
     let res = client
-        .scan(ScanInput {
+        .update_item(UpdateItemInput {
             table_name: get_connections_table(),
+            update_expression: Some(
+                "SET ready_by = :readyBy, slot = :slot, table_id = :table".to_string(),
+            ),
+            condition_expression: Some("#Q = :que and attribute_not_exists(ready_by)".to_string()),
             expression_attribute_names: Some(expression_attribute_names),
             expression_attribute_values: Some(attr_map!(
-            ":val" =>  player_role
+                ":readyBy" => ready_by,
+                ":slot" => seat.slot,
+                ":table" => seat.table_id.clone(),
+                ":que" => true
             )),
-            filter_expression: Some("#R = :val".into()),
-            ..ScanInput::default()
+            key: unresolved_connection.key(),
+            ..UpdateItemInput::default()
         })
-        .await?;
+        .await;
 
-    let items = res.items.ok_or("No players found")?;
-    let player_connections: Vec<_> = items
-        .iter()
-        .map(|player| Connection::from_attrs(player.to_owned()))
-        .map(|player| player.expect("Can't convert player"))
-        .collect();
-    Ok(player_connections)
+    match res {
+        Ok(_) => Ok(true),
+        Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
 }
 
-pub async fn find_next_in_que(role: Role) -> Result<Connection, Error> {
+pub async fn find_expired_ready(now: u64) -> Result<Vec<Connection>, Error> {
+    scan_connections(ScanInput {
+        expression_attribute_values: Some(attr_map!(
+            ":now" => now
+        )),
+        filter_expression: Some("ready_by < :now".into()),
+        ..ScanInput::default()
+    })
+    .await
+}
+
+pub async fn skip_ready(id: String, ready_by: u64) -> Result<bool, Error> {
     let client = DynamoDbClient::new(Default::default());
+    let unresolved_connection = UnresolvedConnection { id };
+
     let mut expression_attribute_names = HashMap::new();
     expression_attribute_names.insert("#R".to_string(), "role".to_string());
     expression_attribute_names.insert("#Q".to_string(), "que".to_string());
 
     let res = client
-        .scan(ScanInput {
+        .update_item(UpdateItemInput {
             table_name: get_connections_table(),
+            key: unresolved_connection.key(),
+            update_expression: Some(
+                "SET #R = :role, #Q = :que \
+                 REMOVE slot, table_id, ready_by, queued_at, series_wins"
+                    .to_string(),
+            ),
+            condition_expression: Some("ready_by = :readyBy".to_string()),
             expression_attribute_names: Some(expression_attribute_names),
             expression_attribute_values: Some(attr_map!(
-            ":val" =>  role,
-            ":que" => true
+                ":role" => Role::Observer,
+                ":que" => false,
+                ":readyBy" => ready_by
             )),
-            filter_expression: Some("#R = :val and #Q = :que".into()),
-            ..ScanInput::default()
+            ..UpdateItemInput::default()
         })
-        .await?;
+        .await;
 
-    let items = res.items.ok_or("No next player found")?;
-    let item = items.first().ok_or("No admin found")?.clone();
-    let admin_item = Connection::from_attrs(item);
+    match res {
+        Ok(_) => Ok(true),
+        Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
 
-    admin_item.map_err(|e| e.into())
+async fn scan_connections(input: ScanInput) -> Result<Vec<Connection>, Error> {
+    let client = DynamoDbClient::new(Default::default());
+    let mut connections = Vec::new();
+    let mut exclusive_start_key = None;
+
+    loop {
+        let res = client
+            .scan(ScanInput {
+                table_name: get_connections_table(),
+                exclusive_start_key,
+                ..input.clone()
+            })
+            .await?;
+
+        for item in res.items.unwrap_or_default() {
+            connections.push(Connection::from_attrs(item)?);
+        }
+
+        exclusive_start_key = res.last_evaluated_key;
+        if exclusive_start_key.is_none() {
+            return Ok(connections);
+        }
+    }
 }
 
-pub async fn mark_player_active(id: String) {
+fn role_filter(filter_expression: &str, role: Role, que: Option<bool>) -> ScanInput {
+    let mut expression_attribute_names = HashMap::new();
+    expression_attribute_names.insert("#R".to_string(), "role".to_string());
+    let mut expression_attribute_values = attr_map!(
+        ":val" => role
+    );
+    if let Some(que) = que {
+        expression_attribute_names.insert("#Q".to_string(), "que".to_string());
+        expression_attribute_values.insert(":que".to_string(), que.into_attr());
+    }
+
+    ScanInput {
+        expression_attribute_names: Some(expression_attribute_names),
+        expression_attribute_values: Some(expression_attribute_values),
+        filter_expression: Some(filter_expression.into()),
+        ..ScanInput::default()
+    }
+}
+
+pub async fn find_active_players(role: Role) -> Result<Vec<Connection>, Error> {
+    scan_connections(role_filter("#R = :val and #Q = :que", role, Some(false))).await
+}
+
+pub async fn find_queued(role: Role) -> Result<Vec<Connection>, Error> {
+    scan_connections(role_filter("#R = :val and #Q = :que", role, Some(true))).await
+}
+
+pub async fn find_unseated(role: Role) -> Result<Vec<Connection>, Error> {
+    scan_connections(role_filter(
+        "#R = :val and #Q = :que and attribute_not_exists(table_id)",
+        role,
+        Some(false),
+    ))
+    .await
+}
+
+pub async fn find_observers() -> Result<Vec<Connection>, Error> {
+    scan_connections(role_filter("#R = :val", Role::Observer, None)).await
+}
+
+pub async fn find_connections() -> Result<Vec<Connection>, Error> {
+    scan_connections(ScanInput::default()).await
+}
+
+pub async fn find_slot_holders(role: Role) -> Result<Vec<Connection>, Error> {
+    scan_connections(role_filter(
+        "#R = :val and (#Q = :que or attribute_exists(ready_by))",
+        role,
+        Some(false),
+    ))
+    .await
+}
+
+pub async fn find_audience(
+    admin: &Connection,
+    audience: Audience,
+) -> Result<Vec<Connection>, Error> {
+    let player_role = admin
+        .role
+        .and_then(|role| role.player_role())
+        .ok_or("Unknown admin")?;
+
+    let connections = match audience {
+        Audience::Observers => in_room(find_observers().await?, &admin.room),
+        Audience::Queued => find_queued(player_role).await?,
+        Audience::Players => find_players(admin)
+            .await?
+            .into_iter()
+            .filter(|player| !player.que && player.ready_by.is_none())
+            .collect(),
+        Audience::Everyone => in_room(find_connections().await?, &admin.room)
+            .into_iter()
+            .filter(|connection| !connection.role.map_or(false, Role::is_admin))
+            .collect(),
+    };
+
+    Ok(connections
+        .into_iter()
+        .filter(|connection| connection.id != admin.id)
+        .collect())
+}
+
+fn in_room(connections: Vec<Connection>, room: &Option<String>) -> Vec<Connection> {
+    match room {
+        Some(_) => connections
+            .into_iter()
+            .filter(|connection| &connection.room == room)
+            .collect(),
+        None => connections,
+    }
+}
+
+pub async fn find_free_seats(role: Role) -> Result<Vec<Seat>, Error> {
+    let holders = find_slot_holders(role).await?;
+    let capacity = game::for_role(role).ok_or("Unknown player")?.capacity();
+
+    let mut tables: Vec<(usize, Vec<Seat>)> = find_admins(role)
+        .await?
+        .into_iter()
+        .map(|admin| {
+            let taken: Vec<i64> = holders
+                .iter()
+                .filter(|player| player.table_id.as_ref() == Some(&admin.id))
+                .filter_map(|player| player.slot)
+                .collect();
+            let free = (0..capacity)
+                .filter(|slot| !taken.contains(slot))
+                .map(|slot| Seat {
+                    table_id: admin.id.clone(),
+                    slot,
+                })
+                .collect();
+            (taken.len(), free)
+        })
+        .collect();
+    tables.sort_by_key(|(taken, _)| std::cmp::Reverse(*taken));
+
+    Ok(tables.into_iter().flat_map(|(_, free)| free).collect())
+}
+
+pub async fn update_buckets(
+    id: String,
+    tokens: f64,
+    byte_tokens: f64,
+    refilled_at: u64,
+    previous: Option<u64>,
+) -> Result<bool, Error> {
     let client = DynamoDbClient::new(Default::default());
     let unresolved_connection = UnresolvedConnection { id };
 
+    let mut expression_attribute_values = attr_map!(
+        ":tokens" => tokens,
+        ":byteTokens" => byte_tokens,
+        ":refilledAt" => refilled_at
+    );
+    let condition_expression = match previous {
+        Some(previous) => {
+            expression_attribute_values.insert(":previous".to_string(), previous.into_attr());
+            "refilled_at = :previous"
+        }
+        None => "attribute_not_exists(refilled_at)",
+    };
+
     let res = client
         .update_item(UpdateItemInput {
             table_name: get_connections_table(),
-            update_expression: Some("SET que = true".to_string()),
             key: unresolved_connection.key(),
+            update_expression: Some(
+                "SET tokens = :tokens, byte_tokens = :byteTokens, refilled_at = :refilledAt"
+                    .to_string(),
+            ),
+            condition_expression: Some(condition_expression.to_string()),
+            expression_attribute_values: Some(expression_attribute_values),
             ..UpdateItemInput::default()
         })
         .await;
 
-    if let Err(err) = res {
-        debug!("error setting que {}", err);
+    match res {
+        Ok(_) => Ok(true),
+        Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub async fn take_vote(id: String, window: u64, limit: i64) -> Result<bool, Error> {
+    let client = DynamoDbClient::new(Default::default());
+    let unresolved_connection = UnresolvedConnection { id };
+
+    let res = client
+        .update_item(UpdateItemInput {
+            table_name: get_connections_table(),
+            key: unresolved_connection.key(),
+            update_expression: Some("SET vote_window = :window, votes = :one".to_string()),
+            condition_expression: Some(
+                "attribute_not_exists(vote_window) or vote_window <> :window".to_string(),
+            ),
+            expression_attribute_values: Some(attr_map!(
+                ":window" => window,
+                ":one" => 1_i64
+            )),
+            ..UpdateItemInput::default()
+        })
+        .await;
+
+    match res {
+        Ok(_) => return Ok(true),
+        Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => {}
+        Err(err) => return Err(err.into()),
+    }
+
+    let res = client
+        .update_item(UpdateItemInput {
+            table_name: get_connections_table(),
+            key: unresolved_connection.key(),
+            update_expression: Some("ADD votes :one".to_string()),
+            condition_expression: Some("vote_window = :window and votes < :limit".to_string()),
+            expression_attribute_values: Some(attr_map!(
+                ":window" => window,
+                ":one" => 1_i64,
+                ":limit" => limit
+            )),
+            ..UpdateItemInput::default()
+        })
+        .await;
+
+    match res {
+        Ok(_) => Ok(true),
+        Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => Ok(false),
+        Err(err) => Err(err.into()),
     }
 }
 
+pub async fn bump_input_seq(id: String) -> Result<u64, Error> {
+    let client = DynamoDbClient::new(Default::default());
+    let unresolved_connection = UnresolvedConnection { id };
+
+    let res = client
+        .update_item(UpdateItemInput {
+            table_name: get_connections_table(),
+            key: unresolved_connection.key(),
+            update_expression: Some("ADD input_seq :one".to_string()),
+            expression_attribute_values: Some(attr_map!(
+                ":one" => 1u64
+            )),
+            return_values: Some("UPDATED_NEW".to_string()),
+            ..UpdateItemInput::default()
+        })
+        .await?;
+
+    let seq = res
+        .attributes
+        .and_then(|attributes| attributes.get("input_seq").cloned())
+        .ok_or("Missing input sequence")?;
+    u64::from_attr(seq).map_err(|e| e.into())
+}
+
 pub async fn has_player(role: Role) -> bool {
     let client = DynamoDbClient::new(Default::default());
     let mut expression_attribute_names = HashMap::new();
@@ -166,14 +565,10 @@ pub async fn has_player(role: Role) -> bool {
 
 pub async fn delete_player(id: String) {
     let client = DynamoDbClient::new(Default::default());
-    let connection = Connection {
-        id,
-        role: None,
-        que: false,
-    };
+    let connection = UnresolvedConnection { id };
     let res = client
         .delete_item(DeleteItemInput {
-            table_name: env::var("tableName").expect("failed to resolve table"),
+            table_name: get_connections_table(),
             key: connection.key(),
             ..DeleteItemInput::default()
         })
@@ -184,12 +579,12 @@ pub async fn delete_player(id: String) {
     }
 }
 
-pub async fn save_player(id: String) {
+pub async fn save_player(connection: Connection) {
     let client = DynamoDbClient::new(Default::default());
     let connection = Connection {
-        id,
         role: Some(Role::Observer),
         que: false,
+        ..connection
     };
 
     let res = client
@@ -205,24 +600,217 @@ pub async fn save_player(id: String) {
     }
 }
 
-pub async fn put_into_que(id: String, role: Role) {
+pub async fn update_role(
+    id: String,
+    role: Role,
+    que: bool,
+    seat: Option<Seat>,
+) -> Result<Connection, Error> {
     let client = DynamoDbClient::new(Default::default());
-    let connection = Connection {
-        id,
-        role: Some(role),
-        que: true,
+    let unresolved_connection = UnresolvedConnection { id };
+
+    let mut expression_attribute_names = HashMap::new();
+    expression_attribute_names.insert("#R".to_string(), "role".to_string());
+    expression_attribute_names.insert("#Q".to_string(), "que".to_string());
+    let mut expression_attribute_values = attr_map!(
+        ":role" => role,
+        ":que" => que
+    );
+    let update_expression = match (seat, que) {
+        (Some(seat), _) => {
+            expression_attribute_values.insert(":slot".to_string(), seat.slot.into_attr());
+            expression_attribute_values.insert(":table".to_string(), seat.table_id.into_attr());
+            expression_attribute_values
+                .insert(":queuedAt".to_string(), time::now_millis().into_attr());
+            "SET #R = :role, #Q = :que, slot = :slot, table_id = :table, \
+             queued_at = if_not_exists(queued_at, :queuedAt) \
+             REMOVE ready_by, clearAt, series_wins"
+        }
+        (None, true) => {
+            expression_attribute_values
+                .insert(":queuedAt".to_string(), time::now_millis().into_attr());
+            "SET #R = :role, #Q = :que, queued_at = :queuedAt \
+             REMOVE slot, table_id, ready_by, clearAt, series_wins"
+        }
+        (None, false) => {
+            "SET #R = :role, #Q = :que \
+             REMOVE slot, table_id, ready_by, clearAt, queued_at, series_wins"
+        }
     };
 
     let res = client
-        .put_item(PutItemInput {
+        .update_item(UpdateItemInput {
             table_name: get_connections_table(),
-            item: connection.into(),
-            ..PutItemInput::default()
+            key: unresolved_connection.key(),
+            update_expression: Some(update_expression.to_string()),
+            expression_attribute_names: Some(expression_attribute_names),
+            expression_attribute_values: Some(expression_attribute_values),
+            return_values: Some("ALL_NEW".to_string()),
+            ..UpdateItemInput::default()
+        })
+        .await?;
+
+    let item = res.attributes.ok_or("Missing Connection")?;
+    Connection::from_attrs(item).map_err(|e| e.into())
+}
+
+pub async fn set_nickname(id: String, nickname: String) -> Result<(), Error> {
+    let client = DynamoDbClient::new(Default::default());
+    let unresolved_connection = UnresolvedConnection { id };
+
+    client
+        .update_item(UpdateItemInput {
+            table_name: get_connections_table(),
+            key: unresolved_connection.key(),
+            update_expression: Some("SET nickname = :nickname".to_string()),
+            expression_attribute_values: Some(attr_map!(
+                ":nickname" => nickname
+            )),
+            ..UpdateItemInput::default()
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_player_id(id: String, player_id: String) -> Result<(), Error> {
+    let client = DynamoDbClient::new(Default::default());
+    let unresolved_connection = UnresolvedConnection { id };
+
+    client
+        .update_item(UpdateItemInput {
+            table_name: get_connections_table(),
+            key: unresolved_connection.key(),
+            update_expression: Some("SET player_id = :player".to_string()),
+            expression_attribute_values: Some(attr_map!(
+                ":player" => player_id
+            )),
+            ..UpdateItemInput::default()
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_series_wins(id: String, series_wins: i64) -> Result<(), Error> {
+    let client = DynamoDbClient::new(Default::default());
+    let unresolved_connection = UnresolvedConnection { id };
+
+    client
+        .update_item(UpdateItemInput {
+            table_name: get_connections_table(),
+            key: unresolved_connection.key(),
+            update_expression: Some("SET series_wins = :wins".to_string()),
+            expression_attribute_values: Some(attr_map!(
+                ":wins" => series_wins
+            )),
+            ..UpdateItemInput::default()
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_tournament(id: String, tournament_id: Option<String>) -> Result<(), Error> {
+    let client = DynamoDbClient::new(Default::default());
+    let unresolved_connection = UnresolvedConnection { id };
+
+    let (update_expression, expression_attribute_values) = match tournament_id {
+        Some(tournament_id) => (
+            "SET tournament_id = :tournament",
+            Some(attr_map!(":tournament" => tournament_id)),
+        ),
+        None => ("REMOVE tournament_id", None),
+    };
+
+    let res = client
+        .update_item(UpdateItemInput {
+            table_name: get_connections_table(),
+            key: unresolved_connection.key(),
+            update_expression: Some(update_expression.to_string()),
+            condition_expression: Some("attribute_exists(id)".to_string()),
+            expression_attribute_values,
+            ..UpdateItemInput::default()
+        })
+        .await;
+
+    match res {
+        Ok(_) | Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub async fn put_into_que(id: String, role: Role) {
+    if let Err(err) = update_role(id, role, true, None).await {
+        debug!("error putting connection into que {:?}", err);
+    }
+}
+
+pub async fn return_to_que(id: String, role: Role) {
+    let client = DynamoDbClient::new(Default::default());
+    let unresolved_connection = UnresolvedConnection { id };
+
+    let mut expression_attribute_names = HashMap::new();
+    expression_attribute_names.insert("#R".to_string(), "role".to_string());
+    expression_attribute_names.insert("#Q".to_string(), "que".to_string());
+
+    let res = client
+        .update_item(UpdateItemInput {
+            table_name: get_connections_table(),
+            key: unresolved_connection.key(),
+            update_expression: Some(
+                "SET #R = :role, #Q = :que, queued_at = if_not_exists(queued_at, :queuedAt) \
+                 REMOVE slot, table_id, ready_by, clearAt, series_wins"
+                    .to_string(),
+            ),
+            expression_attribute_names: Some(expression_attribute_names),
+            expression_attribute_values: Some(attr_map!(
+                ":role" => role,
+                ":que" => true,
+                ":queuedAt" => time::now_millis()
+            )),
+            ..UpdateItemInput::default()
         })
         .await;
 
     if let Err(err) = res {
-        debug!("error creating connection {:?}", err);
+        debug!("error returning connection to que {:?}", err);
+    }
+}
+
+pub async fn seat_player(id: String, seat: &Seat) -> Result<bool, Error> {
+    let client = DynamoDbClient::new(Default::default());
+    let unresolved_connection = UnresolvedConnection { id };
+
+    let mut expression_attribute_names = HashMap::new();
+    expression_attribute_names.insert("#Q".to_string(), "que".to_string());
+
+    let res = client
+        .update_item(UpdateItemInput {
+            table_name: get_connections_table(),
+            key: unresolved_connection.key(),
+            update_expression: Some("SET slot = :slot, table_id = :table".to_string()),
+            condition_expression: Some("#Q = :que and attribute_not_exists(table_id)".to_string()),
+            expression_attribute_names: Some(expression_attribute_names),
+            expression_attribute_values: Some(attr_map!(
+                ":slot" => seat.slot,
+                ":table" => seat.table_id.clone(),
+                ":que" => false
+            )),
+            ..UpdateItemInput::default()
+        })
+        .await;
+
+    match res {
+        Ok(_) => Ok(true),
+        Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub async fn reset_to_observer(id: String) {
+    if let Err(err) = update_role(id, Role::Observer, false, None).await {
+        debug!("error resetting connection {:?}", err);
     }
 }
 
@@ -249,6 +837,33 @@ pub async fn get_player_count_by_role(role: Role) -> Result<i64, Error> {
     n_existing.map_err(|e| e.into())
 }
 
+pub async fn count_connections_by_ip(source_ip: String) -> Result<i64, Error> {
+    let client = DynamoDbClient::new(Default::default());
+    let mut count = 0;
+    let mut exclusive_start_key = None;
+
+    loop {
+        let res = client
+            .scan(ScanInput {
+                table_name: get_connections_table(),
+                select: Some("COUNT".into()),
+                expression_attribute_values: Some(attr_map!(
+                    ":ip" => source_ip.clone()
+                )),
+                filter_expression: Some("source_ip = :ip".into()),
+                exclusive_start_key,
+                ..ScanInput::default()
+            })
+            .await?;
+
+        count += res.count.unwrap_or(0);
+        exclusive_start_key = res.last_evaluated_key;
+        if exclusive_start_key.is_none() {
+            return Ok(count);
+        }
+    }
+}
+
 pub async fn save_connection(connection: Connection) -> Result<Connection, Error> {
     let client = DynamoDbClient::new(Default::default());
 
@@ -0,0 +1,158 @@
+use crate::config;
+use crate::error::Error;
+use crate::models::{Connection, Cooldown, Role};
+use crate::store;
+use crate::time;
+use dynomite::attr_map;
+
+const KIND: &str = "cooldown";
+
+// The address is only a fallback for connections without a device identity, since a
+// venue's audience often shares one public address.
+fn subject(connection: &Connection) -> String {
+    match (&connection.player_id, &connection.source_ip) {
+        (Some(player_id), _) => format!("player#{}", player_id),
+        (None, Some(ip)) => format!("ip#{}", ip),
+        (None, None) => format!("connection#{}", connection.id),
+    }
+}
+
+// Per-connection cooldowns, such as the canvas, never fall back to the address.
+fn device_subject(connection: &Connection) -> String {
+    match &connection.player_id {
+        Some(player_id) => format!("player#{}", player_id),
+        None => format!("connection#{}", connection.id),
+    }
+}
+
+fn cooldown_id(scope: &str, subject: &str) -> String {
+    format!("{}#{}#{}", KIND, scope, subject)
+}
+
+pub async fn start(connection: &Connection, role: Role) -> Result<(), Error> {
+    start_scope(connection, role.name(), Some(role), config::cooldown(role)).await
+}
+
+pub async fn remaining(connection: &Connection, role: Role) -> Result<Option<u64>, Error> {
+    remaining_scope(connection, role.name()).await
+}
+
+pub async fn start_scope(
+    connection: &Connection,
+    scope: &str,
+    role: Option<Role>,
+    duration: u64,
+) -> Result<(), Error> {
+    if duration == 0 {
+        return Ok(());
+    }
+
+    let subject = subject(connection);
+    store::put_record(Cooldown {
+        id: cooldown_id(scope, &subject),
+        kind: KIND.to_string(),
+        role,
+        subject,
+        expires_at: Some(time::now_seconds() + duration),
+    })
+    .await
+}
+
+// Starts the cooldown unless it is still running, in which case the time left is returned.
+pub async fn claim_scope(
+    connection: &Connection,
+    scope: &str,
+    duration: u64,
+) -> Result<Option<u64>, Error> {
+    if duration == 0 {
+        return Ok(None);
+    }
+
+    let subject = device_subject(connection);
+    claim(Cooldown {
+        id: cooldown_id(scope, &subject),
+        kind: KIND.to_string(),
+        role: None,
+        subject,
+        expires_at: Some(time::now_seconds() + duration),
+    })
+    .await
+}
+
+async fn claim(cooldown: Cooldown) -> Result<Option<u64>, Error> {
+    loop {
+        if store::create_record(cooldown.clone()).await? {
+            return Ok(None);
+        }
+
+        let existing: Option<Cooldown> = store::get_record(cooldown.id.clone()).await?;
+        let previous = match existing.and_then(|existing| existing.expires_at) {
+            Some(previous) => previous,
+            None => continue,
+        };
+        let now = time::now_seconds();
+        if previous > now {
+            return Ok(Some(previous - now));
+        }
+        if store::put_record_if(cooldown.clone(), attr_map!("clearAt" => previous)).await? {
+            return Ok(None);
+        }
+    }
+}
+
+pub async fn remaining_scope(connection: &Connection, scope: &str) -> Result<Option<u64>, Error> {
+    let cooldown: Option<Cooldown> =
+        store::get_record(cooldown_id(scope, &subject(connection))).await?;
+    let now = time::now_seconds();
+    Ok(cooldown
+        .and_then(|cooldown| cooldown.expires_at)
+        .filter(|expires_at| *expires_at > now)
+        .map(|expires_at| expires_at - now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connection(player_id: Option<&str>, source_ip: Option<&str>) -> Connection {
+        Connection {
+            id: "abc=".to_string(),
+            player_id: player_id.map(str::to_string),
+            source_ip: source_ip.map(str::to_string),
+            ..Connection::default()
+        }
+    }
+
+    #[test]
+    fn identity_wins_over_a_shared_address() {
+        assert_eq!(
+            subject(&connection(Some("p1"), Some("203.0.113.7"))),
+            "player#p1"
+        );
+        assert_eq!(
+            subject(&connection(Some("p2"), Some("203.0.113.7"))),
+            "player#p2"
+        );
+    }
+
+    #[test]
+    fn address_is_the_fallback_without_an_identity() {
+        assert_eq!(
+            subject(&connection(None, Some("203.0.113.7"))),
+            "ip#203.0.113.7"
+        );
+        assert_eq!(subject(&connection(None, None)), "connection#abc=");
+    }
+
+    #[test]
+    fn device_cooldowns_ignore_the_address() {
+        assert_eq!(
+            device_subject(&connection(Some("p1"), Some("203.0.113.7"))),
+            "player#p1"
+        );
+        assert_eq!(
+            device_subject(&connection(None, Some("203.0.113.7"))),
+            "connection#abc="
+        );
+    }
+}
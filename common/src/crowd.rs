@@ -0,0 +1,145 @@
+use crate::aggregate;
+use crate::config;
+use crate::connection_operations::*;
+use crate::error::Error;
+use crate::game;
+use crate::models::*;
+use crate::send;
+use crate::store;
+use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequestContext;
+use serde::{Deserialize, Serialize};
+
+const KIND: &str = "crowd";
+const TALLY_KIND: &str = "crowd_tally";
+const MAX_INPUT_LENGTH: usize = 32;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CrowdInput {
+    input: String,
+}
+
+fn mode_id(table_id: &str) -> String {
+    format!("{}#{}", KIND, table_id)
+}
+
+pub async fn open(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+    options: Option<Vec<String>>,
+) -> Result<(), Error> {
+    store::put_record(CrowdMode {
+        id: mode_id(&admin.id),
+        kind: KIND.to_string(),
+        table_id: admin.id.clone(),
+        options: options.clone(),
+    })
+    .await?;
+
+    let options = options.unwrap_or_default();
+    for observer in find_audience(admin, Audience::Observers).await? {
+        send::crowd(request_context.clone(), observer.id, Some(&options)).await;
+    }
+    Ok(())
+}
+
+pub async fn close(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+) -> Result<(), Error> {
+    let mode: Option<CrowdMode> = store::get_record(mode_id(&admin.id)).await?;
+    if mode.is_none() {
+        return Ok(());
+    }
+
+    store::delete_record(mode_id(&admin.id)).await?;
+    for observer in find_audience(admin, Audience::Observers).await? {
+        send::crowd(request_context.clone(), observer.id, None).await;
+    }
+    Ok(())
+}
+
+pub async fn vote(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection: &Connection,
+    message: &str,
+) -> Result<(), Error> {
+    let input = serde_json::from_str::<CrowdInput>(message)?.input;
+
+    let admin = match crowd_admin(connection).await? {
+        Some(admin) => admin,
+        None => {
+            send::vote_rejected(request_context, "no_room").await;
+            return Ok(());
+        }
+    };
+    let mode: Option<CrowdMode> = store::get_record(mode_id(&admin.id)).await?;
+    let mode = match mode {
+        Some(mode) => mode,
+        None => {
+            send::vote_rejected(request_context, "crowd_closed").await;
+            return Ok(());
+        }
+    };
+
+    let allowed = match &mode.options {
+        Some(options) => options.contains(&input),
+        None => !input.is_empty() && input.chars().count() <= MAX_INPUT_LENGTH,
+    };
+    if !allowed {
+        send::vote_rejected(request_context, "invalid_input").await;
+        return Ok(());
+    }
+
+    let tick = config::crowd_tick();
+    let window = aggregate::current_window(tick);
+    if !take_vote(connection.id.clone(), window, config::crowd_vote_limit()).await? {
+        send::vote_rejected(request_context, "vote_limit").await;
+        return Ok(());
+    }
+
+    aggregate::add(TALLY_KIND, &admin.id, window, input).await?;
+    let (batches, _) = aggregate::flush_table(TALLY_KIND, &admin.id, tick).await?;
+    send_tallies(request_context, batches).await;
+    Ok(())
+}
+
+pub async fn flush_due(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+) -> Result<(), Error> {
+    let batches = aggregate::flush_due(TALLY_KIND, config::crowd_tick()).await?;
+    send_tallies(request_context, batches).await;
+    Ok(())
+}
+
+async fn send_tallies(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    batches: Vec<aggregate::Batch>,
+) {
+    for batch in batches {
+        send::crowd_tally(
+            request_context.clone(),
+            batch.table_id,
+            batch.window,
+            &batch.counts,
+        )
+        .await;
+    }
+}
+
+async fn crowd_admin(connection: &Connection) -> Result<Option<Connection>, Error> {
+    if let Some(room) = &connection.room {
+        return Ok(Some(find_room_admin(room.clone()).await?));
+    }
+
+    // Observers who joined without a code vote on the one table running crowd mode.
+    let mut open = Vec::new();
+    for mode in game::modes() {
+        for admin in find_admins(mode.player_role()).await? {
+            let crowd: Option<CrowdMode> = store::get_record(mode_id(&admin.id)).await?;
+            if crowd.is_some() {
+                open.push(admin);
+            }
+        }
+    }
+    Ok(if open.len() == 1 { open.pop() } else { None })
+}
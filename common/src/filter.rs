@@ -0,0 +1,114 @@
+use std::env;
+
+pub fn words() -> Vec<String> {
+    env::var("wordFilter")
+        .unwrap_or_default()
+        .split(',')
+        .map(|word| word.trim().to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+fn unleet(c: char) -> char {
+    match c {
+        '0' => 'o',
+        '1' => 'i',
+        '3' => 'e',
+        '4' => 'a',
+        '5' => 's',
+        '7' => 't',
+        _ => c,
+    }
+}
+
+// Splits on separators and camel case humps, and joins spelled out letters back
+// together, so "d a r n" and "DarnFox" both contain "darn" but "classic" is one word.
+fn tokens(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut previous_lower = false;
+    for c in text.chars() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(word.split_off(0));
+            }
+            previous_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && previous_lower && !word.is_empty() {
+            words.push(word.split_off(0));
+        }
+        previous_lower = c.is_lowercase();
+        word.extend(c.to_lowercase().map(unleet));
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    let mut tokens: Vec<String> = Vec::new();
+    let mut letters = String::new();
+    for word in words {
+        if word.chars().count() == 1 {
+            letters.push_str(&word);
+            continue;
+        }
+        if !letters.is_empty() {
+            tokens.push(letters.split_off(0));
+        }
+        tokens.push(word);
+    }
+    if !letters.is_empty() {
+        tokens.push(letters);
+    }
+    tokens
+}
+
+pub fn is_blocked(text: &str, words: &[String]) -> bool {
+    let text = tokens(text);
+    words.iter().any(|word| {
+        let word = tokens(word);
+        !word.is_empty() && text.windows(word.len()).any(|window| window == &word[..])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words() -> Vec<String> {
+        vec!["darn".to_string(), "heck".to_string(), "ass".to_string()]
+    }
+
+    #[test]
+    fn catches_disguised_words() {
+        assert!(is_blocked("darn", &words()));
+        assert!(is_blocked("DARN it", &words()));
+        assert!(is_blocked("d a r n", &words()));
+        assert!(is_blocked("d.a.r.n", &words()));
+        assert!(is_blocked("h3ck", &words()));
+        assert!(is_blocked("oh-heck!", &words()));
+        assert!(is_blocked("DarnFox", &words()));
+        assert!(!is_blocked("hello there", &words()));
+    }
+
+    #[test]
+    fn matches_whole_words_only() {
+        assert!(!is_blocked("classic", &words()));
+        assert!(!is_blocked("glass sword", &words()));
+        assert!(!is_blocked("dar nice", &words()));
+        assert!(!is_blocked("checked", &words()));
+    }
+
+    #[test]
+    fn phrases_match_consecutive_words() {
+        let phrase = vec!["bad word".to_string()];
+        assert!(is_blocked("such a Bad-Word", &phrase));
+        assert!(!is_blocked("bad luck, word", &phrase));
+    }
+
+    #[test]
+    fn empty_filter_blocks_nothing() {
+        assert!(!is_blocked("darn", &[]));
+        assert!(!is_blocked("darn", &[" ".to_string()]));
+    }
+}
@@ -0,0 +1,198 @@
+use crate::canvas;
+use crate::config::{self, Matchmaking};
+use crate::crowd;
+use crate::error::Error;
+use crate::models::*;
+use crate::quiz;
+use crate::roles;
+use crate::tournament;
+use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequestContext;
+use serde::Deserialize;
+use std::future::Future;
+use std::pin::Pin;
+
+pub type Hook<'a> = Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueuePolicy {
+    Arrival,
+    Rating,
+    Takeover,
+}
+
+pub trait GameMode: Sync {
+    fn name(&self) -> &'static str;
+    fn player_role(&self) -> Role;
+    fn admin_role(&self) -> Role;
+    fn capacity(&self) -> i64;
+    fn queue_policy(&self) -> QueuePolicy;
+
+    fn multiple_tables(&self) -> bool {
+        true
+    }
+
+    fn validate(&self, _message: &str) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn on_open<'a>(
+        &'a self,
+        _request_context: ApiGatewayWebsocketProxyRequestContext,
+        _admin: &'a Connection,
+    ) -> Hook<'a> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn on_close<'a>(
+        &'a self,
+        request_context: ApiGatewayWebsocketProxyRequestContext,
+        admin: &'a Connection,
+    ) -> Hook<'a> {
+        Box::pin(crowd::close(request_context, admin))
+    }
+}
+
+pub struct Pong;
+
+impl GameMode for Pong {
+    fn name(&self) -> &'static str {
+        "pong"
+    }
+
+    fn player_role(&self) -> Role {
+        Role::PlayerPong
+    }
+
+    fn admin_role(&self) -> Role {
+        Role::AdminPong
+    }
+
+    fn capacity(&self) -> i64 {
+        config::capacity(self.name(), 2)
+    }
+
+    fn queue_policy(&self) -> QueuePolicy {
+        match config::pong_matchmaking() {
+            Matchmaking::Rating => QueuePolicy::Rating,
+            Matchmaking::Arrival => QueuePolicy::Arrival,
+        }
+    }
+
+    fn on_close<'a>(
+        &'a self,
+        request_context: ApiGatewayWebsocketProxyRequestContext,
+        admin: &'a Connection,
+    ) -> Hook<'a> {
+        Box::pin(async move {
+            tournament::cancel(request_context.clone(), admin).await?;
+            crowd::close(request_context, admin).await
+        })
+    }
+}
+
+pub struct Display;
+
+impl GameMode for Display {
+    fn name(&self) -> &'static str {
+        "display"
+    }
+
+    fn player_role(&self) -> Role {
+        Role::PlayerDisplay
+    }
+
+    fn admin_role(&self) -> Role {
+        Role::AdminDisplay
+    }
+
+    fn capacity(&self) -> i64 {
+        config::capacity(self.name(), 1)
+    }
+
+    fn queue_policy(&self) -> QueuePolicy {
+        QueuePolicy::Takeover
+    }
+
+    fn multiple_tables(&self) -> bool {
+        false
+    }
+
+    fn on_open<'a>(
+        &'a self,
+        request_context: ApiGatewayWebsocketProxyRequestContext,
+        admin: &'a Connection,
+    ) -> Hook<'a> {
+        Box::pin(canvas::send_snapshot(request_context, admin.id.clone()))
+    }
+
+    fn on_close<'a>(
+        &'a self,
+        request_context: ApiGatewayWebsocketProxyRequestContext,
+        admin: &'a Connection,
+    ) -> Hook<'a> {
+        Box::pin(async move {
+            quiz::stop(request_context.clone(), admin).await?;
+            crowd::close(request_context, admin).await
+        })
+    }
+}
+
+// A mode defined here may use Role::Custom for its roles; its role names resolve through
+// the registry like configured ones.
+static BUILT_IN: &[&dyn GameMode] = &[&Pong, &Display];
+
+pub fn built_in() -> &'static [&'static dyn GameMode] {
+    BUILT_IN
+}
+
+pub fn modes() -> &'static [&'static dyn GameMode] {
+    roles::modes()
+}
+
+pub fn for_role(role: Role) -> Option<&'static dyn GameMode> {
+    modes()
+        .iter()
+        .copied()
+        .find(|mode| mode.player_role() == role || mode.admin_role() == role)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_modes_pair_their_own_roles() {
+        for mode in built_in() {
+            assert_ne!(mode.player_role(), mode.admin_role());
+            assert!(mode.player_role().is_player());
+            assert!(mode.admin_role().is_admin());
+            assert_eq!(mode.admin_role().player_role(), Some(mode.player_role()));
+        }
+    }
+
+    #[test]
+    fn finds_the_mode_for_either_role() {
+        assert_eq!(
+            for_role(Role::PlayerPong).map(|mode| mode.name()),
+            Some("pong")
+        );
+        assert_eq!(
+            for_role(Role::AdminPong).map(|mode| mode.name()),
+            Some("pong")
+        );
+        assert_eq!(
+            for_role(Role::AdminDisplay).map(|mode| mode.name()),
+            Some("display")
+        );
+        assert!(for_role(Role::Observer).is_none());
+    }
+
+    #[test]
+    fn modes_keep_their_table_and_queue_rules() {
+        assert!(Pong.multiple_tables());
+        assert!(!Display.multiple_tables());
+        assert_eq!(Display.queue_policy(), QueuePolicy::Takeover);
+        assert_eq!(Pong.validate("anything"), Ok(()));
+    }
+}
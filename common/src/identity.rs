@@ -0,0 +1,76 @@
+use hmac::{Hmac, Mac, NewMac};
+use rand::Rng;
+use sha2::Sha256;
+use std::env;
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn secret() -> String {
+    env::var("identitySecret").unwrap_or_default()
+}
+
+fn mac(secret: &str, player_id: &str) -> Option<HmacSha256> {
+    if secret.is_empty() {
+        return None;
+    }
+    let mut mac = HmacSha256::new_varkey(secret.as_bytes()).ok()?;
+    mac.update(player_id.as_bytes());
+    Some(mac)
+}
+
+pub fn issue() -> Option<(String, String)> {
+    sign(&secret(), hex::encode(rand::thread_rng().gen::<[u8; 16]>()))
+}
+
+pub fn verify(token: &str) -> Option<String> {
+    check(&secret(), token)
+}
+
+fn sign(secret: &str, player_id: String) -> Option<(String, String)> {
+    let signature = mac(secret, &player_id)?.finalize().into_bytes();
+    let token = format!("{}.{}", player_id, hex::encode(signature));
+    Some((player_id, token))
+}
+
+fn check(secret: &str, token: &str) -> Option<String> {
+    let mut parts = token.splitn(2, '.');
+    let player_id = parts.next()?;
+    let signature = hex::decode(parts.next()?).ok()?;
+    mac(secret, player_id)?.verify(&signature).ok()?;
+    Some(player_id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn issued_tokens_verify() {
+        let (player_id, token) = sign("secret", "p1".to_string()).unwrap();
+        assert_eq!(player_id, "p1");
+        assert_eq!(check("secret", &token), Some("p1".to_string()));
+    }
+
+    #[test]
+    fn rejects_tampered_and_foreign_tokens() {
+        let (_, token) = sign("secret", "p1".to_string()).unwrap();
+        let signature = &token[token.find('.').unwrap() + 1..];
+        assert_eq!(check("secret", &format!("p2.{}", signature)), None);
+        assert_eq!(check("other", &token), None);
+    }
+
+    #[test]
+    fn rejects_malformed_tokens() {
+        assert_eq!(check("secret", ""), None);
+        assert_eq!(check("secret", "p1"), None);
+        assert_eq!(check("secret", "p1.not-hex"), None);
+        assert_eq!(check("secret", "p1.00"), None);
+    }
+
+    #[test]
+    fn identities_are_off_without_a_secret() {
+        assert_eq!(sign("", "p1".to_string()), None);
+        let (_, token) = sign("secret", "p1".to_string()).unwrap();
+        assert_eq!(check("", &token), None);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::error::Error;
+use qrcode::render::svg;
+use qrcode::{Color, QrCode};
+use rand::Rng;
+use serde::Deserialize;
+use std::env;
+
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LENGTH: usize = 5;
+const QUIET_ZONE: usize = 4;
+const MODULE_SIZE: usize = 8;
+
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QrFormat {
+    Svg,
+    Png,
+}
+
+pub struct Qr {
+    pub mime: &'static str,
+    pub data: Vec<u8>,
+}
+
+pub fn generate_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..CODE_LENGTH)
+        .map(|_| CODE_ALPHABET[rng.gen_range(0, CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+pub fn join_url(code: &str) -> String {
+    let base = env::var("joinUrl").unwrap_or_default();
+    format!("{}?code={}", base, code)
+}
+
+pub fn qr_code(data: &str, format: QrFormat) -> Result<Qr, Error> {
+    let code = QrCode::new(data.as_bytes())?;
+
+    match format {
+        QrFormat::Svg => {
+            let image = code.render::<svg::Color>().min_dimensions(256, 256).build();
+            Ok(Qr {
+                mime: "image/svg+xml",
+                data: image.into_bytes(),
+            })
+        }
+        QrFormat::Png => Ok(Qr {
+            mime: "image/png",
+            data: render_png(&code)?,
+        }),
+    }
+}
+
+fn render_png(code: &QrCode) -> Result<Vec<u8>, Error> {
+    let modules = code.width();
+    let colors = code.to_colors();
+    let size = (modules + QUIET_ZONE * 2) * MODULE_SIZE;
+
+    let mut pixels = vec![255u8; size * size];
+    for (index, color) in colors.iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+        let x = (index % modules + QUIET_ZONE) * MODULE_SIZE;
+        let y = (index / modules + QUIET_ZONE) * MODULE_SIZE;
+        for row in y..y + MODULE_SIZE {
+            for pixel in &mut pixels[row * size + x..row * size + x + MODULE_SIZE] {
+                *pixel = 0;
+            }
+        }
+    }
+
+    let mut bytes = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut bytes, size as u32, size as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&pixels)?;
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_use_the_unambiguous_alphabet() {
+        for _ in 0..100 {
+            let code = generate_code();
+            assert_eq!(code.len(), CODE_LENGTH);
+            assert!(code.bytes().all(|c| CODE_ALPHABET.contains(&c)));
+        }
+        assert!(!CODE_ALPHABET.contains(&b'O') && !CODE_ALPHABET.contains(&b'0'));
+        assert!(!CODE_ALPHABET.contains(&b'I') && !CODE_ALPHABET.contains(&b'1'));
+    }
+
+    #[test]
+    fn renders_png_with_a_quiet_zone() {
+        let qr = qr_code("https://example.com/join?code=ABCDE", QrFormat::Png).unwrap();
+        assert_eq!(qr.mime, "image/png");
+        assert!(qr.data.starts_with(b"\x89PNG\r\n\x1a\n"));
+
+        let modules = QrCode::new(b"https://example.com/join?code=ABCDE")
+            .unwrap()
+            .width();
+        let width = u32::from_be_bytes([qr.data[16], qr.data[17], qr.data[18], qr.data[19]]);
+        assert_eq!(width as usize, (modules + QUIET_ZONE * 2) * MODULE_SIZE);
+    }
+
+    #[test]
+    fn renders_svg() {
+        let qr = qr_code("ABCDE", QrFormat::Svg).unwrap();
+        assert_eq!(qr.mime, "image/svg+xml");
+        assert!(String::from_utf8(qr.data).unwrap().contains("<svg"));
+    }
+}
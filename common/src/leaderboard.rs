@@ -0,0 +1,80 @@
+use crate::config;
+use crate::error::Error;
+use crate::models::{Connection, PlayerStats};
+use crate::rating;
+use crate::store;
+use std::collections::HashMap;
+
+const KIND: &str = "stats";
+
+fn display_name(player: &Connection) -> String {
+    player.nickname.clone().unwrap_or_else(|| player.id.clone())
+}
+
+fn stats_id(player: &Connection) -> String {
+    match &player.player_id {
+        Some(player_id) => format!("{}#player#{}", KIND, player_id),
+        None => format!("{}#{}", KIND, display_name(player).to_lowercase()),
+    }
+}
+
+async fn find_stats(player: &Connection) -> Result<PlayerStats, Error> {
+    let stats: Option<PlayerStats> = store::get_record(stats_id(player)).await?;
+    let mut stats = stats.unwrap_or_else(|| PlayerStats {
+        id: stats_id(player),
+        kind: KIND.to_string(),
+        ..PlayerStats::default()
+    });
+    stats.nickname = display_name(player);
+    Ok(stats)
+}
+
+pub async fn record(winner: &Connection, loser: &Connection) -> Result<(), Error> {
+    let mut winner_stats = find_stats(winner).await?;
+    winner_stats.wins += 1;
+    winner_stats.streak = winner_stats.streak.max(0) + 1;
+    winner_stats.best_streak = winner_stats.best_streak.max(winner_stats.streak);
+
+    let mut loser_stats = find_stats(loser).await?;
+    loser_stats.losses += 1;
+    loser_stats.streak = loser_stats.streak.min(0) - 1;
+
+    let (winner_rating, loser_rating) = rating::update(
+        winner_stats.rating.unwrap_or_else(config::initial_rating),
+        loser_stats.rating.unwrap_or_else(config::initial_rating),
+        config::rating_k(),
+    );
+    winner_stats.rating = Some(winner_rating);
+    loser_stats.rating = Some(loser_rating);
+
+    store::put_record(winner_stats).await?;
+    store::put_record(loser_stats).await
+}
+
+pub async fn top(limit: usize) -> Result<Vec<PlayerStats>, Error> {
+    let mut stats: Vec<PlayerStats> = store::scan_records(KIND).await?;
+    stats.sort_by(|a, b| {
+        b.wins
+            .cmp(&a.wins)
+            .then(a.losses.cmp(&b.losses))
+            .then(b.best_streak.cmp(&a.best_streak))
+    });
+    stats.truncate(limit);
+    Ok(stats)
+}
+
+pub async fn ratings(players: &[&Connection]) -> Result<HashMap<String, f64>, Error> {
+    let ids = players.iter().map(|player| stats_id(player)).collect();
+    let stats: Vec<PlayerStats> = store::get_records(ids).await?;
+    Ok(stats
+        .into_iter()
+        .filter_map(|stats| stats.rating.map(|rating| (stats.id, rating)))
+        .collect())
+}
+
+pub fn rating_of(ratings: &HashMap<String, f64>, player: &Connection) -> f64 {
+    ratings
+        .get(&stats_id(player))
+        .copied()
+        .unwrap_or_else(config::initial_rating)
+}
@@ -1,4 +1,35 @@
+pub mod aggregate;
+pub mod bans;
+pub mod bracket;
+pub mod canvas;
+pub mod config;
 pub mod connection_operations;
+pub mod cooldown;
+pub mod crowd;
+pub mod filter;
+pub mod game;
+pub mod identity;
+pub mod join;
+pub mod leaderboard;
+pub mod matchmaking;
+pub mod metadata;
 pub mod models;
+pub mod nickname;
+pub mod poll;
+pub mod permissions;
+pub mod pong;
+pub mod que;
+pub mod quiz;
+pub mod rate_limit;
+pub mod rating;
+pub mod reactions;
+pub mod render;
+pub mod roles;
 pub mod send;
+pub mod slots;
+pub mod snapshots;
+pub mod store;
+pub mod submissions;
+pub mod time;
+pub mod tournament;
 pub mod error;
@@ -0,0 +1,49 @@
+use crate::config;
+use crate::connection_operations::*;
+use crate::error::Error;
+use crate::game::{self, QueuePolicy};
+use crate::leaderboard;
+use crate::models::*;
+use crate::time;
+
+pub async fn next_player(role: Role, seat: &Seat) -> Result<Connection, Error> {
+    let policy = game::for_role(role).map(|mode| mode.queue_policy());
+    if policy != Some(QueuePolicy::Rating) {
+        return find_next_in_que(role).await;
+    }
+
+    let queued: Vec<Connection> = find_que(role)
+        .await?
+        .into_iter()
+        .filter(|player| player.ready_by.is_none() && player.tournament_id.is_none())
+        .collect();
+    let oldest = queued.first().cloned().ok_or("No next player found")?;
+
+    let waited = time::now_millis().saturating_sub(oldest.queued_at.unwrap_or(0));
+    if waited >= config::matchmaking_max_wait() * 1000 {
+        return Ok(oldest);
+    }
+
+    // An empty table seats the longest waiting player, and the other seat is then
+    // filled with the closest rated partner for them.
+    let opponent = match find_slot_holders(role).await?.into_iter().find(|holder| {
+        holder.table_id.as_ref() == Some(&seat.table_id) && holder.slot != Some(seat.slot)
+    }) {
+        Some(opponent) => opponent,
+        None => return Ok(oldest),
+    };
+
+    let candidates: Vec<&Connection> = queued.iter().chain(Some(&opponent)).collect();
+    let ratings = leaderboard::ratings(&candidates).await?;
+    let target = leaderboard::rating_of(&ratings, &opponent);
+    let gap = |player: &Connection| (leaderboard::rating_of(&ratings, player) - target).abs();
+
+    Ok(queued
+        .into_iter()
+        .min_by(|a, b| {
+            gap(a)
+                .partial_cmp(&gap(b))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .unwrap_or(oldest))
+}
@@ -0,0 +1,48 @@
+use crate::config::{self, IpPrivacy};
+use sha2::{Digest, Sha256};
+use std::env;
+
+pub fn source_ip(ip: Option<String>) -> Option<String> {
+    let salt = env::var("ipHashSalt").unwrap_or_default();
+    protect(config::ip_privacy(), &salt, ip)
+}
+
+fn protect(privacy: IpPrivacy, salt: &str, ip: Option<String>) -> Option<String> {
+    match privacy {
+        IpPrivacy::Keep => ip,
+        IpPrivacy::Hash => ip.map(|ip| hash_ip(salt, &ip)),
+        IpPrivacy::Drop => None,
+    }
+}
+
+fn hash_ip(salt: &str, ip: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(ip.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip() -> Option<String> {
+        Some("203.0.113.7".to_string())
+    }
+
+    #[test]
+    fn keeps_or_drops_the_address() {
+        assert_eq!(protect(IpPrivacy::Keep, "salt", ip()), ip());
+        assert_eq!(protect(IpPrivacy::Drop, "salt", ip()), None);
+        assert_eq!(protect(IpPrivacy::Hash, "salt", None), None);
+    }
+
+    #[test]
+    fn hashes_are_stable_and_salted() {
+        let hashed = protect(IpPrivacy::Hash, "salt", ip()).unwrap();
+        assert_eq!(hashed.len(), 64);
+        assert_ne!(Some(hashed.clone()), ip());
+        assert_eq!(protect(IpPrivacy::Hash, "salt", ip()), Some(hashed.clone()));
+        assert_ne!(protect(IpPrivacy::Hash, "pepper", ip()), Some(hashed));
+    }
+}
@@ -1,36 +1,373 @@
-use dynomite::{Attribute, Item};
-use serde::{Deserialize, Serialize};
+use crate::game;
+use crate::roles;
+use dynomite::dynamodb::AttributeValue;
+use dynomite::{Attribute, AttributeError, Attributes, FromAttributes, Item};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 
-#[derive(Attribute, Debug, Serialize, Deserialize, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Role {
     Observer,
     PlayerPong,
     PlayerDisplay,
     AdminPong,
     AdminDisplay,
+    Custom(&'static str),
 }
 
 impl FromStr for Role {
-    type Err = ();
-
-    fn from_str(s: &str) -> Result<Role, ()> {
-        match s {
-            "PlayerPong" => Ok(Role::PlayerPong),
-            "PlayerDisplay" => Ok(Role::PlayerDisplay),
-            "AdminPong" => Ok(Role::AdminPong),
-            "AdminDisplay" => Ok(Role::AdminDisplay),
-            _ => Ok(Role::Observer),
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Role, String> {
+        roles::find(s).ok_or_else(|| format!("Unknown role {}", s))
+    }
+}
+
+impl Attribute for Role {
+    fn into_attr(self) -> AttributeValue {
+        self.name().to_string().into_attr()
+    }
+
+    // A stored role whose definition was since removed reads back as an observer, so
+    // scans over old rows keep working. Clients and config still get the strict parse.
+    fn from_attr(value: AttributeValue) -> Result<Self, AttributeError> {
+        Ok(String::from_attr(value)?.parse().unwrap_or(Role::Observer))
+    }
+}
+
+impl Serialize for Role {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Role {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Role, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Role {
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Observer => "Observer",
+            Role::PlayerPong => "PlayerPong",
+            Role::PlayerDisplay => "PlayerDisplay",
+            Role::AdminPong => "AdminPong",
+            Role::AdminDisplay => "AdminDisplay",
+            Role::Custom(name) => name,
         }
     }
+
+    pub fn admin_role(self) -> Option<Role> {
+        game::modes()
+            .iter()
+            .find(|mode| mode.player_role() == self)
+            .map(|mode| mode.admin_role())
+    }
+
+    pub fn player_role(self) -> Option<Role> {
+        game::modes()
+            .iter()
+            .find(|mode| mode.admin_role() == self)
+            .map(|mode| mode.player_role())
+    }
+
+    pub fn is_admin(self) -> bool {
+        self.player_role().is_some()
+    }
+
+    pub fn is_player(self) -> bool {
+        self.admin_role().is_some()
+    }
+
+    pub fn permissions(self) -> &'static [String] {
+        roles::permissions(self)
+    }
+
+    pub fn is_privileged(self) -> bool {
+        self.is_admin() || !self.permissions().is_empty()
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Item, Clone)]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Audience {
+    Observers,
+    Queued,
+    Players,
+    Everyone,
+}
+
+#[derive(Serialize, Deserialize, Debug, Item, Clone, Default)]
 pub struct Connection {
     #[dynomite(partition_key)]
     pub id: String,
     pub role: Option<Role>,
     pub que: bool,
+    pub slot: Option<i64>,
+    pub ready_by: Option<u64>,
+    pub tokens: Option<f64>,
+    pub byte_tokens: Option<f64>,
+    pub refilled_at: Option<u64>,
+    pub input_seq: Option<u64>,
+    pub room: Option<String>,
+    pub source_ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub device: Option<String>,
+    pub connected_at: Option<u64>,
+    pub nickname: Option<String>,
+    pub queued_at: Option<u64>,
+    pub series_wins: Option<i64>,
+    pub table_id: Option<String>,
+    pub tournament_id: Option<String>,
+    pub player_id: Option<String>,
+    pub vote_window: Option<u64>,
+    pub votes: Option<i64>,
+}
+
+impl Connection {
+    pub fn seat(&self) -> Option<Seat> {
+        match (&self.table_id, self.slot) {
+            (Some(table_id), Some(slot)) => Some(Seat {
+                table_id: table_id.clone(),
+                slot,
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Seat {
+    pub table_id: String,
+    pub slot: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Item, Clone, Default)]
+pub struct SlotClaim {
+    #[dynomite(partition_key)]
+    pub id: String,
+    pub kind: String,
+    pub table_id: String,
+    pub slot: i64,
+    pub holder: String,
+    pub claimed_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Item, Clone, Default)]
+pub struct Ban {
+    #[dynomite(partition_key)]
+    pub id: String,
+    pub kind: String,
+    pub target: String,
+    pub value: String,
+    pub reason: Option<String>,
+    #[dynomite(rename = "clearAt")]
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Item, Clone, Default)]
+pub struct PlayerStats {
+    #[dynomite(partition_key)]
+    pub id: String,
+    pub kind: String,
+    pub nickname: String,
+    pub wins: i64,
+    pub losses: i64,
+    pub streak: i64,
+    pub best_streak: i64,
+    pub rating: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Item, Clone, Default)]
+pub struct Cooldown {
+    #[dynomite(partition_key)]
+    pub id: String,
+    pub kind: String,
+    pub role: Option<Role>,
+    pub subject: String,
+    #[dynomite(rename = "clearAt")]
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Item, Clone, Default)]
+pub struct CrowdMode {
+    #[dynomite(partition_key)]
+    pub id: String,
+    pub kind: String,
+    pub table_id: String,
+    pub options: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Item, Clone, Default)]
+pub struct Tally {
+    #[dynomite(partition_key)]
+    pub id: String,
+    pub kind: String,
+    pub table_id: String,
+    pub window: u64,
+    pub choice: String,
+    pub votes: i64,
+    #[dynomite(rename = "clearAt")]
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Item, Clone, Default)]
+pub struct Poll {
+    #[dynomite(partition_key)]
+    pub id: String,
+    pub kind: String,
+    pub table_id: String,
+    pub room: Option<String>,
+    pub question: String,
+    pub options: Vec<String>,
+    pub deadline: u64,
+    pub closed: bool,
+    pub results: Option<HashMap<String, i64>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Item, Clone, Default)]
+pub struct MemberSet {
+    #[dynomite(partition_key)]
+    pub id: String,
+    pub kind: String,
+    pub members: Option<HashSet<String>>,
+    #[dynomite(rename = "clearAt")]
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Item, Clone, Default)]
+pub struct TallyWindow {
+    #[dynomite(partition_key)]
+    pub id: String,
+    pub kind: String,
+    pub table_id: String,
+    pub window: u64,
+    pub choices: Option<HashSet<String>>,
+    #[dynomite(rename = "clearAt")]
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Item, Clone, Default)]
+pub struct PollTally {
+    #[dynomite(partition_key)]
+    pub id: String,
+    pub kind: String,
+    pub poll_id: String,
+    pub choice: String,
+    pub votes: i64,
+    #[dynomite(rename = "clearAt")]
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Item, Clone, Default)]
+pub struct Ballot {
+    #[dynomite(partition_key)]
+    pub id: String,
+    pub kind: String,
+    pub poll_id: String,
+    pub choice: String,
+    #[dynomite(rename = "clearAt")]
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Item, Clone, Default)]
+pub struct SubmissionQueue {
+    #[dynomite(partition_key)]
+    pub id: String,
+    pub kind: String,
+    pub table_id: String,
+    pub ids: Option<HashSet<String>>,
+    #[dynomite(rename = "clearAt")]
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Item, Clone, Default)]
+pub struct Submission {
+    #[dynomite(partition_key)]
+    pub id: String,
+    pub kind: String,
+    pub table_id: String,
+    pub connection_id: String,
+    pub player_id: Option<String>,
+    pub nickname: Option<String>,
+    pub content_type: String,
+    pub body: String,
+    pub status: String,
+    pub reason: Option<String>,
+    pub delivered: bool,
+    pub submitted_at: u64,
+    #[dynomite(rename = "clearAt")]
+    pub expires_at: Option<u64>,
+}
+
+pub const PIXEL_PREFIX: &str = "p";
+
+// Canvas chunks keep one attribute per pixel, named by its index on the canvas.
+#[derive(Debug, Clone, Default)]
+pub struct CanvasChunk {
+    pub pixels: Vec<(usize, u8)>,
+}
+
+impl FromAttributes for CanvasChunk {
+    fn from_attrs(attrs: Attributes) -> Result<Self, AttributeError> {
+        let mut pixels = Vec::new();
+        for (name, value) in attrs {
+            if let Some(index) = name
+                .strip_prefix(PIXEL_PREFIX)
+                .and_then(|index| index.parse().ok())
+            {
+                pixels.push((index, u8::from_attr(value)?));
+            }
+        }
+        Ok(CanvasChunk { pixels })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Item, Clone, Default)]
+pub struct Tournament {
+    #[dynomite(partition_key)]
+    pub id: String,
+    pub kind: String,
+    pub table_id: String,
+    pub bracket: String,
+    #[dynomite(rename = "clearAt")]
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Item, Clone, Default)]
+pub struct Quiz {
+    #[dynomite(partition_key)]
+    pub id: String,
+    pub kind: String,
+    pub table_id: String,
+    pub run: String,
+    pub title: Option<String>,
+    pub questions: String,
+    pub current: Option<i64>,
+    pub asked_at: u64,
+    pub deadline: u64,
+    pub round_open: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Item, Clone, Default)]
+pub struct QuizAnswer {
+    #[dynomite(partition_key)]
+    pub id: String,
+    pub kind: String,
+    pub run: String,
+    pub question: i64,
+    pub connection_id: String,
+    pub nickname: Option<String>,
+    pub choice: i64,
+    pub points: i64,
+    pub answered_at: u64,
+    #[dynomite(rename = "clearAt")]
+    pub expires_at: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Item, Clone)]
@@ -0,0 +1,86 @@
+use crate::filter;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+const MIN_LENGTH: usize = 2;
+const MAX_LENGTH: usize = 16;
+
+const ADJECTIVES: &[&str] = &[
+    "Brave", "Calm", "Clever", "Cosmic", "Eager", "Fuzzy", "Gentle", "Happy", "Jolly", "Lucky",
+    "Mighty", "Nimble", "Quick", "Shiny", "Sneaky", "Sunny", "Swift", "Witty",
+];
+
+const ANIMALS: &[&str] = &[
+    "Badger", "Bear", "Falcon", "Fox", "Gecko", "Heron", "Koala", "Lynx", "Otter", "Panda",
+    "Puffin", "Rabbit", "Raven", "Seal", "Tiger", "Turtle", "Walrus", "Wolf",
+];
+
+pub fn validate(nickname: &str, blocked: &[String]) -> Result<String, &'static str> {
+    let nickname = nickname.trim();
+    let length = nickname.chars().count();
+
+    if !(MIN_LENGTH..=MAX_LENGTH).contains(&length) {
+        return Err("length");
+    }
+    if !nickname
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == ' ' || c == '_' || c == '-')
+    {
+        return Err("characters");
+    }
+    if filter::is_blocked(nickname, blocked) {
+        return Err("blocked");
+    }
+    Ok(nickname.to_string())
+}
+
+pub fn generate() -> String {
+    let mut rng = rand::thread_rng();
+    format!(
+        "{}{}{}",
+        ADJECTIVES.choose(&mut rng).unwrap_or(&"Happy"),
+        ANIMALS.choose(&mut rng).unwrap_or(&"Otter"),
+        rng.gen_range(10, 100)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_and_trims_valid_names() {
+        assert_eq!(validate("  Otter_42 ", &[]), Ok("Otter_42".to_string()));
+        assert_eq!(
+            validate("Jo-Anne Smith", &[]),
+            Ok("Jo-Anne Smith".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_bad_lengths() {
+        assert_eq!(validate("a", &[]), Err("length"));
+        assert_eq!(validate("   ", &[]), Err("length"));
+        assert_eq!(validate("abcdefghijklmnopq", &[]), Err("length"));
+    }
+
+    #[test]
+    fn rejects_symbols() {
+        assert_eq!(validate("<script>", &[]), Err("characters"));
+        assert_eq!(validate("name!", &[]), Err("characters"));
+    }
+
+    #[test]
+    fn rejects_blocked_words() {
+        let blocked = vec!["darn".to_string()];
+        assert_eq!(validate("DarnFox", &blocked), Err("blocked"));
+        assert!(validate("Darnell", &blocked).is_ok());
+    }
+
+    #[test]
+    fn generated_names_are_valid() {
+        for _ in 0..100 {
+            assert!(validate(&generate(), &[]).is_ok());
+        }
+    }
+}
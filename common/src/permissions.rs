@@ -0,0 +1,375 @@
+use crate::error::Error;
+use crate::models::*;
+use crate::roles;
+use crate::send;
+use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequestContext;
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use std::env;
+
+const GROUPS: &[&str] = &["any", "observers", "players", "admins"];
+// Message types the handlers derive from the payload shape; a client-supplied "type" is
+// never trusted.
+const MESSAGE_TYPES: &[(&str, &str)] = &[
+    ("upstream", "vote"),
+    ("upstream", "game"),
+    ("downstream", "poll"),
+    ("downstream", "direct"),
+    ("downstream", "audience"),
+    ("downstream", "players"),
+];
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Permission {
+    pub route: String,
+    #[serde(rename = "type")]
+    pub message_type: Option<String>,
+    pub senders: Vec<String>,
+    #[serde(default)]
+    pub recipients: Vec<String>,
+}
+
+static MATRIX: OnceCell<Vec<Permission>> = OnceCell::new();
+
+fn permission(route: &str, senders: &[&str], recipients: &[&str]) -> Permission {
+    Permission {
+        route: route.to_string(),
+        message_type: None,
+        senders: senders.iter().map(|sender| sender.to_string()).collect(),
+        recipients: recipients
+            .iter()
+            .map(|recipient| recipient.to_string())
+            .collect(),
+    }
+}
+
+pub fn defaults() -> Vec<Permission> {
+    let mut matrix = vec![
+        permission("upstream", &["any"], &["admins"]),
+        permission("downstream", &["admins"], &["observers", "players"]),
+        Permission {
+            message_type: Some("poll".to_string()),
+            ..permission("downstream", &["AdminDisplay"], &["observers", "players"])
+        },
+        permission("match_result", &["AdminPong"], &[]),
+        permission("tournament_start", &["AdminPong"], &[]),
+        permission("quiz_load", &["AdminDisplay"], &[]),
+        permission("quiz_next", &["AdminDisplay"], &[]),
+        permission("quiz_stop", &["AdminDisplay"], &[]),
+    ];
+    for route in &[
+        "selection",
+        "leave_queue",
+        "release_role",
+        "switch_role",
+        "ready",
+        "leaderboard",
+        "vote",
+        "polls",
+        "reaction",
+        "submit",
+        "pixel",
+        "canvas",
+        "answer",
+    ] {
+        matrix.push(permission(route, &["any"], &[]));
+    }
+    for route in &[
+        "snapshot",
+        "ban",
+        "unban",
+        "bans",
+        "que",
+        "tournament_cancel",
+        "bracket",
+        "crowd",
+        "submissions",
+        "moderate",
+        "render",
+    ] {
+        matrix.push(permission(route, &["admins"], &[]));
+    }
+    matrix
+}
+
+pub fn init() -> Result<(), Error> {
+    MATRIX.get_or_try_init(load)?;
+    Ok(())
+}
+
+fn matrix() -> &'static [Permission] {
+    MATRIX.get_or_init(|| load().unwrap_or_else(|err| panic!("Invalid permissions: {}", err)))
+}
+
+fn load() -> Result<Vec<Permission>, Error> {
+    let mut matrix = defaults();
+    let overrides = env::var("permissionMatrix").unwrap_or_default();
+    if overrides.is_empty() {
+        return Ok(matrix);
+    }
+
+    let overrides: Vec<Permission> = serde_json::from_str(&overrides)
+        .map_err(|err| format!("Cannot parse permissionMatrix: {}", err))?;
+    validate(&overrides)?;
+    for entry in overrides {
+        matrix.retain(|existing| {
+            existing.route != entry.route || existing.message_type != entry.message_type
+        });
+        matrix.push(entry);
+    }
+    Ok(matrix)
+}
+
+pub fn validate(entries: &[Permission]) -> Result<(), String> {
+    for entry in entries {
+        if !roles::ROUTES.contains(&entry.route.as_str()) {
+            return Err(format!("Permission for unknown route {}", entry.route));
+        }
+        if let Some(message_type) = &entry.message_type {
+            if !MESSAGE_TYPES.contains(&(entry.route.as_str(), message_type.as_str())) {
+                return Err(format!(
+                    "Permission for {} has unknown message type {}",
+                    entry.route, message_type
+                ));
+            }
+        }
+        if let Some(name) = entry
+            .senders
+            .iter()
+            .chain(entry.recipients.iter())
+            .find(|name| !GROUPS.contains(&name.as_str()) && roles::find(name).is_none())
+        {
+            return Err(format!(
+                "Permission for {} names unknown role {}",
+                entry.route, name
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn matches(names: &[String], role: Option<Role>) -> bool {
+    names.iter().any(|name| match name.as_str() {
+        "any" => true,
+        "observers" => role.map_or(true, |role| role == Role::Observer),
+        "players" => role.map_or(false, Role::is_player),
+        "admins" => role.map_or(false, Role::is_admin),
+        name => role.map_or(false, |role| role.name() == name),
+    })
+}
+
+fn entry(route: &str, message_type: Option<&str>) -> Option<&'static Permission> {
+    let matrix = matrix();
+    message_type
+        .and_then(|message_type| {
+            matrix.iter().find(|entry| {
+                entry.route == route && entry.message_type.as_deref() == Some(message_type)
+            })
+        })
+        .or_else(|| {
+            matrix
+                .iter()
+                .find(|entry| entry.route == route && entry.message_type.is_none())
+        })
+}
+
+pub fn can_use(sender: Option<Role>, route: &str, message_type: Option<&str>) -> bool {
+    let granted = sender.map_or(false, |role| {
+        role.permissions()
+            .iter()
+            .any(|permission| permission == route)
+    });
+    granted || entry(route, message_type).map_or(false, |entry| matches(&entry.senders, sender))
+}
+
+pub fn can_reach(route: &str, message_type: Option<&str>, recipient: Option<Role>) -> bool {
+    entry(route, message_type).map_or(false, |entry| matches(&entry.recipients, recipient))
+}
+
+pub fn upstream_type(sender: &Connection) -> &'static str {
+    match sender.role {
+        None | Some(Role::Observer) => "vote",
+        Some(_) => "game",
+    }
+}
+
+// Seated players belong to the table they sit at, unseated players to every table of
+// their game, and observers to the admin's room.
+fn belongs_to(admin: &Connection, connection: &Connection) -> bool {
+    if let Some(table_id) = &connection.table_id {
+        return table_id == &admin.id;
+    }
+    match connection.role {
+        None | Some(Role::Observer) => admin.room.is_none() || connection.room == admin.room,
+        Some(role) => admin.role.and_then(Role::player_role) == Some(role),
+    }
+}
+
+pub fn same_table(sender: &Connection, recipient: &Connection) -> bool {
+    let is_admin = |connection: &Connection| connection.role.map_or(false, Role::is_admin);
+    match (is_admin(sender), is_admin(recipient)) {
+        (true, false) => belongs_to(sender, recipient),
+        (false, true) => belongs_to(recipient, sender),
+        _ => sender.id == recipient.id,
+    }
+}
+
+pub async fn check(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    sender: &Connection,
+    route: &str,
+    message_type: Option<&str>,
+) -> bool {
+    if can_use(sender.role, route, message_type) {
+        return true;
+    }
+    send::permission_denied(request_context, route, message_type, None).await;
+    false
+}
+
+pub async fn check_recipient(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    sender: &Connection,
+    route: &str,
+    message_type: Option<&str>,
+    recipient: &Connection,
+) -> bool {
+    if same_table(sender, recipient) && can_reach(route, message_type, recipient.role) {
+        return true;
+    }
+    send::permission_denied(request_context, route, message_type, Some(&recipient.id)).await;
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(route: &str, message_type: Option<&str>, senders: &[&str]) -> Permission {
+        Permission {
+            message_type: message_type.map(str::to_string),
+            ..permission(route, senders, &[])
+        }
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert_eq!(validate(&defaults()), Ok(()));
+    }
+
+    #[test]
+    fn rejects_unknown_routes_roles_and_types() {
+        assert!(validate(&[rule("fly", None, &["any"])])
+            .unwrap_err()
+            .contains("unknown route"));
+        assert!(validate(&[rule("ban", None, &["Ghost"])])
+            .unwrap_err()
+            .contains("unknown role Ghost"));
+        assert!(validate(&[rule("downstream", Some("chat"), &["any"])])
+            .unwrap_err()
+            .contains("unknown message type"));
+        for (route, message_type) in MESSAGE_TYPES {
+            assert_eq!(
+                validate(&[rule(route, Some(message_type), &["AdminPong"])]),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn senders_match_groups_and_roles() {
+        assert!(can_use(None, "selection", None));
+        assert!(can_use(None, "upstream", None));
+        assert!(!can_use(None, "downstream", None));
+        assert!(!can_use(Some(Role::Observer), "ban", None));
+        assert!(can_use(Some(Role::AdminDisplay), "ban", None));
+        assert!(can_use(Some(Role::AdminPong), "match_result", None));
+        assert!(!can_use(Some(Role::AdminDisplay), "match_result", None));
+        assert!(!can_use(Some(Role::PlayerPong), "quiz_next", None));
+    }
+
+    #[test]
+    fn typed_entries_override_the_route() {
+        assert!(can_use(Some(Role::AdminPong), "downstream", None));
+        assert!(!can_use(Some(Role::AdminPong), "downstream", Some("poll")));
+        assert!(can_use(
+            Some(Role::AdminDisplay),
+            "downstream",
+            Some("poll")
+        ));
+    }
+
+    #[test]
+    fn recipients_match_groups() {
+        assert!(can_reach("upstream", None, Some(Role::AdminPong)));
+        assert!(!can_reach("upstream", None, Some(Role::PlayerPong)));
+        assert!(can_reach("downstream", None, None));
+        assert!(can_reach("downstream", None, Some(Role::PlayerDisplay)));
+        assert!(!can_reach("downstream", None, Some(Role::AdminPong)));
+    }
+
+    fn connection(id: &str, role: Option<Role>) -> Connection {
+        Connection {
+            id: id.to_string(),
+            role,
+            ..Connection::default()
+        }
+    }
+
+    #[test]
+    fn upstream_types_follow_the_sender() {
+        assert_eq!(upstream_type(&connection("a", None)), "vote");
+        assert_eq!(
+            upstream_type(&connection("a", Some(Role::Observer))),
+            "vote"
+        );
+        assert_eq!(
+            upstream_type(&connection("a", Some(Role::PlayerPong))),
+            "game"
+        );
+    }
+
+    #[test]
+    fn seated_players_only_reach_their_own_table() {
+        let admin = connection("table-a", Some(Role::AdminPong));
+        let mut player = connection("p", Some(Role::PlayerPong));
+        player.table_id = Some("table-a".to_string());
+        assert!(same_table(&admin, &player));
+        assert!(same_table(&player, &admin));
+
+        player.table_id = Some("table-b".to_string());
+        assert!(!same_table(&admin, &player));
+        assert!(!same_table(&player, &admin));
+    }
+
+    #[test]
+    fn unseated_players_belong_to_their_game() {
+        let admin = connection("table-a", Some(Role::AdminPong));
+        assert!(same_table(&admin, &connection("p", Some(Role::PlayerPong))));
+        assert!(!same_table(
+            &admin,
+            &connection("p", Some(Role::PlayerDisplay))
+        ));
+    }
+
+    #[test]
+    fn observers_belong_to_the_admin_room() {
+        let mut admin = connection("table-a", Some(Role::AdminDisplay));
+        let mut observer = connection("o", Some(Role::Observer));
+        assert!(same_table(&admin, &observer));
+
+        admin.room = Some("ABCD".to_string());
+        assert!(!same_table(&admin, &observer));
+        observer.room = Some("ABCD".to_string());
+        assert!(same_table(&admin, &observer));
+    }
+
+    #[test]
+    fn admins_cannot_reach_other_admins() {
+        let admin = connection("table-a", Some(Role::AdminPong));
+        assert!(!same_table(
+            &admin,
+            &connection("table-b", Some(Role::AdminDisplay))
+        ));
+    }
+}
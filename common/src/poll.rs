@@ -0,0 +1,204 @@
+use crate::connection_operations::*;
+use crate::error::Error;
+use crate::filter;
+use crate::models::*;
+use crate::send;
+use crate::store;
+use crate::time;
+use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequestContext;
+use dynomite::attr_map;
+use rand::Rng;
+use std::collections::HashMap;
+
+const KIND: &str = "poll";
+const TALLY_KIND: &str = "poll_tally";
+const BALLOT_KIND: &str = "poll_ballot";
+const BALLOT_RETENTION: u64 = 24 * 60 * 60;
+const MAX_OPTIONS: usize = 10;
+
+pub async fn start(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+    question: String,
+    options: Vec<String>,
+    duration: u64,
+    recipients: Vec<String>,
+) -> Result<Poll, Error> {
+    if question.trim().is_empty() || filter::is_blocked(&question, &filter::words()) {
+        return Err("Invalid poll question".into());
+    }
+    if !(2..=MAX_OPTIONS).contains(&options.len()) {
+        return Err("Polls need between 2 and 10 options".into());
+    }
+    if duration == 0 {
+        return Err("Poll deadline must be in the future".into());
+    }
+
+    let poll = Poll {
+        id: format!(
+            "{}#{}",
+            KIND,
+            hex::encode(rand::thread_rng().gen::<[u8; 8]>())
+        ),
+        kind: KIND.to_string(),
+        table_id: admin.id.clone(),
+        room: admin.room.clone(),
+        question,
+        options,
+        deadline: time::now_seconds() + duration,
+        closed: false,
+        results: None,
+    };
+    store::put_record(poll.clone()).await?;
+
+    for recipient in recipients {
+        send::poll(request_context.clone(), recipient, &poll).await;
+    }
+    send::poll_tally(
+        request_context,
+        admin.id.clone(),
+        &poll,
+        &tallies(&poll).await?,
+    )
+    .await;
+    Ok(poll)
+}
+
+pub async fn vote(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection: &Connection,
+    poll_id: String,
+    choice: String,
+) -> Result<(), Error> {
+    let poll: Option<Poll> = store::get_record(poll_id).await?;
+    let poll = match poll {
+        Some(poll) => poll,
+        None => {
+            send::vote_rejected(request_context, "unknown_poll").await;
+            return Ok(());
+        }
+    };
+    if poll.closed || time::now_seconds() >= poll.deadline {
+        if !poll.closed {
+            close(request_context.clone(), poll).await?;
+        }
+        send::vote_rejected(request_context, "poll_closed").await;
+        return Ok(());
+    }
+    if !poll.options.contains(&choice) {
+        send::vote_rejected(request_context, "invalid_input").await;
+        return Ok(());
+    }
+
+    let voter = match &connection.player_id {
+        Some(player_id) => format!("player#{}", player_id),
+        None => format!("connection#{}", connection.id),
+    };
+    let first_vote = store::create_record(Ballot {
+        id: format!("{}#{}#{}", BALLOT_KIND, poll.id, voter),
+        kind: BALLOT_KIND.to_string(),
+        poll_id: poll.id.clone(),
+        choice: choice.clone(),
+        expires_at: Some(poll.deadline + BALLOT_RETENTION),
+    })
+    .await?;
+    if !first_vote {
+        send::vote_rejected(request_context, "already_voted").await;
+        return Ok(());
+    }
+
+    store::increment_record(
+        tally_id(&poll.id, &choice),
+        "votes",
+        attr_map!(
+            "kind" => TALLY_KIND.to_string(),
+            "poll_id" => poll.id.clone(),
+            "choice" => choice,
+            "clearAt" => poll.deadline + BALLOT_RETENTION
+        ),
+    )
+    .await?;
+
+    let votes = tallies(&poll).await?;
+    send::poll_tally(request_context, poll.table_id.clone(), &poll, &votes).await;
+    Ok(())
+}
+
+pub async fn close(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    mut poll: Poll,
+) -> Result<(), Error> {
+    let votes = tallies(&poll).await?;
+    // Only the caller that flips the flag broadcasts, so a late vote and the scheduler
+    // closing the same poll send the results once.
+    let closed = store::update_record_if(
+        poll.id.clone(),
+        attr_map!("closed" => true, "results" => votes.clone()),
+        attr_map!("closed" => false),
+    )
+    .await?;
+    if !closed {
+        return Ok(());
+    }
+    poll.closed = true;
+    poll.results = Some(votes.clone());
+
+    let admin = find_connection_in_db(UnresolvedConnection {
+        id: poll.table_id.clone(),
+    })
+    .await;
+    if let Ok(admin) = admin {
+        send::poll_tally(request_context.clone(), admin.id.clone(), &poll, &votes).await;
+        for observer in find_audience(&admin, Audience::Observers).await? {
+            send::poll(request_context.clone(), observer.id, &poll).await;
+        }
+    }
+    Ok(())
+}
+
+pub async fn close_due(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+) -> Result<(), Error> {
+    let now = time::now_seconds();
+    let due: Vec<Poll> = store::scan_records::<Poll>(KIND)
+        .await?
+        .into_iter()
+        .filter(|poll| !poll.closed && poll.deadline <= now)
+        .collect();
+
+    for poll in due {
+        close(request_context.clone(), poll).await?;
+    }
+    Ok(())
+}
+
+pub async fn results() -> Result<Vec<Poll>, Error> {
+    let mut polls: Vec<Poll> = store::scan_records::<Poll>(KIND)
+        .await?
+        .into_iter()
+        .filter(|poll| poll.closed)
+        .collect();
+    polls.sort_by_key(|poll| poll.deadline);
+    Ok(polls)
+}
+
+fn tally_id(poll_id: &str, choice: &str) -> String {
+    format!("{}#{}#{}", TALLY_KIND, poll_id, choice)
+}
+
+async fn tallies(poll: &Poll) -> Result<HashMap<String, i64>, Error> {
+    let mut votes: HashMap<String, i64> = poll
+        .options
+        .iter()
+        .map(|option| (option.clone(), 0))
+        .collect();
+    let ids = poll
+        .options
+        .iter()
+        .map(|option| tally_id(&poll.id, option))
+        .collect();
+    for tally in store::get_records::<PollTally>(ids).await? {
+        votes.insert(tally.choice, tally.votes);
+    }
+    Ok(votes)
+}
@@ -0,0 +1,73 @@
+use crate::config::{self, Rotation};
+use crate::connection_operations::*;
+use crate::error::Error;
+use crate::leaderboard;
+use crate::models::*;
+use crate::que;
+use crate::send;
+use crate::tournament;
+use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequestContext;
+
+pub async fn record_result(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+    winner_slot: i64,
+    loser_slot: i64,
+) -> Result<(), Error> {
+    if winner_slot == loser_slot {
+        return Err("Winner and loser must be different slots".into());
+    }
+    let players: Vec<Connection> = find_players(admin)
+        .await?
+        .into_iter()
+        .filter(|player| !player.que && player.ready_by.is_none())
+        .collect();
+    let winner = players
+        .iter()
+        .find(|player| player.slot == Some(winner_slot))
+        .ok_or("Unknown winner slot")?;
+    let loser = players
+        .iter()
+        .find(|player| player.slot == Some(loser_slot))
+        .ok_or("Unknown loser slot")?;
+
+    if let Some(bracket) = tournament::active(&admin.id).await? {
+        return tournament::record_result(request_context, admin, bracket, winner, loser).await;
+    }
+    leaderboard::record(winner, loser).await?;
+
+    match config::pong_rotation() {
+        Rotation::WinnerStays => {
+            rotate_out(request_context, &[loser]).await?;
+        }
+        Rotation::BothRotate => {
+            rotate_out(request_context, &[winner, loser]).await?;
+        }
+        Rotation::BestOf(games) => {
+            let series_wins = winner.series_wins.unwrap_or(0) + 1;
+            if series_wins > games / 2 {
+                set_series_wins(winner.id.clone(), 0).await?;
+                rotate_out(request_context, &[loser]).await?;
+            } else {
+                set_series_wins(winner.id.clone(), series_wins).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Everyone leaving the table is requeued before any seat is refilled, so the
+// replacements are matched against each other rather than a departing player.
+async fn rotate_out(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    players: &[&Connection],
+) -> Result<(), Error> {
+    for player in players {
+        put_into_que(player.id.clone(), Role::PlayerPong).await;
+        send::rotated(request_context.clone(), player).await;
+    }
+    for player in players {
+        que::rotate_slot(request_context.clone(), player).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,240 @@
+use crate::config;
+use crate::connection_operations::*;
+use crate::cooldown;
+use crate::error::Error;
+use crate::game;
+use crate::matchmaking;
+use crate::models::*;
+use crate::send;
+use crate::slots;
+use crate::time;
+use crate::tournament;
+use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequestContext;
+use log::debug;
+
+const PROMOTE_ATTEMPTS: usize = 3;
+
+pub async fn release_slot(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection: &Connection,
+    status: &str,
+) -> Result<(), Error> {
+    if let Some(table_id) = &connection.tournament_id {
+        tournament::withdraw(request_context.clone(), table_id, &connection.id).await?;
+        set_tournament(connection.id.clone(), None).await?;
+    }
+
+    let role = match connection.role {
+        Some(role) if role.is_player() => role,
+        Some(role) if role.is_admin() => return close_table(request_context, connection).await,
+        _ => return Ok(()),
+    };
+
+    if connection.ready_by.is_some() {
+        slots::vacate(connection).await?;
+        promote_next(request_context, role, connection.seat()).await;
+    } else if !connection.que {
+        cooldown::start(connection, role).await?;
+        hand_over(request_context, connection, role, status).await?;
+    }
+    Ok(())
+}
+
+// Rotated players go straight back into the queue, so they skip the requeue cooldown.
+pub async fn rotate_slot(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection: &Connection,
+) -> Result<(), Error> {
+    match connection.role {
+        Some(role) if role.is_player() => {
+            hand_over(request_context, connection, role, "ROTATED").await
+        }
+        _ => Ok(()),
+    }
+}
+
+async fn hand_over(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection: &Connection,
+    role: Role,
+    status: &str,
+) -> Result<(), Error> {
+    if let Ok(admin) = find_table_admin(connection).await {
+        send::inform_server(
+            request_context.clone(),
+            connection,
+            admin.id,
+            status.to_string(),
+        )
+        .await;
+    }
+    slots::vacate(connection).await?;
+    promote_next(request_context, role, connection.seat()).await;
+    Ok(())
+}
+
+pub async fn promote_next(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    role: Role,
+    seat: Option<Seat>,
+) {
+    let seat = match seat {
+        Some(seat) => seat,
+        None => return,
+    };
+    if let Ok(Some(_)) = tournament::active(&seat.table_id).await {
+        return;
+    }
+    for _ in 0..PROMOTE_ATTEMPTS {
+        let mut player = match matchmaking::next_player(role, &seat).await {
+            Ok(player) => player,
+            Err(_) => return,
+        };
+        match slots::claim(&seat, &player.id).await {
+            Ok(true) => {}
+            _ => return,
+        }
+
+        let timeout = config::ready_timeout();
+        let ready_by = time::now_seconds() + timeout;
+        if let Ok(true) = await_ready(player.id.clone(), &seat, ready_by).await {
+            player.slot = Some(seat.slot);
+            player.table_id = Some(seat.table_id.clone());
+            player.ready_by = Some(ready_by);
+            send::your_turn(request_context, &player, timeout).await;
+            return;
+        }
+        // Someone else promoted this player first; free the seat and pick again.
+        if let Err(err) = slots::release(&seat, &player.id).await {
+            debug!("error releasing slot {}", err);
+            return;
+        }
+    }
+}
+
+pub async fn activate(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    mut player: Connection,
+) -> Result<(), Error> {
+    mark_player_active(player.id.clone()).await;
+    player.que = false;
+    player.ready_by = None;
+
+    send::role_accepted(request_context.clone(), &player).await;
+    if let Ok(admin) = find_table_admin(&player).await {
+        send::inform_server(request_context, &player, admin.id, "CONNECTED".to_string()).await;
+    }
+    Ok(())
+}
+
+pub async fn skip(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    player: &Connection,
+) -> Result<(), Error> {
+    let (role, ready_by) = match (player.role, player.ready_by) {
+        (Some(role), Some(ready_by)) => (role, ready_by),
+        _ => return Ok(()),
+    };
+    if !skip_ready(player.id.clone(), ready_by).await? {
+        return Ok(());
+    }
+
+    send::skipped(request_context.clone(), player.id.clone()).await;
+    if let Some(table_id) = &player.tournament_id {
+        tournament::withdraw(request_context.clone(), table_id, &player.id).await?;
+        set_tournament(player.id.clone(), None).await?;
+    }
+    slots::vacate(player).await?;
+    promote_next(request_context, role, player.seat()).await;
+    Ok(())
+}
+
+pub async fn skip_expired(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+) -> Result<(), Error> {
+    for player in find_expired_ready(time::now_seconds()).await? {
+        skip(request_context.clone(), &player).await?;
+    }
+    Ok(())
+}
+
+pub async fn fill_table(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+) -> Result<(), Error> {
+    let mode = admin.role.and_then(game::for_role).ok_or("Unknown admin")?;
+    let role = mode.player_role();
+    let taken: Vec<i64> = find_players(admin)
+        .await?
+        .iter()
+        .filter_map(|player| player.slot)
+        .collect();
+
+    let mut unseated = find_unseated(role).await?;
+    unseated.sort_by_key(|player| std::cmp::Reverse(player.connected_at));
+    for slot in (0..mode.capacity()).filter(|slot| !taken.contains(slot)) {
+        let seat = Seat {
+            table_id: admin.id.clone(),
+            slot,
+        };
+        if !adopt(request_context.clone(), admin, &seat, &mut unseated).await? {
+            promote_next(request_context.clone(), role, Some(seat)).await;
+        }
+    }
+    Ok(())
+}
+
+// Players who took the role while no table was open sit down before anyone is promoted.
+async fn adopt(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+    seat: &Seat,
+    unseated: &mut Vec<Connection>,
+) -> Result<bool, Error> {
+    while let Some(mut player) = unseated.pop() {
+        if !slots::claim(seat, &player.id).await? {
+            unseated.push(player);
+            return Ok(false);
+        }
+        if seat_player(player.id.clone(), seat).await? {
+            player.slot = Some(seat.slot);
+            player.table_id = Some(seat.table_id.clone());
+            send::role_accepted(request_context.clone(), &player).await;
+            send::inform_server(
+                request_context,
+                &player,
+                admin.id.clone(),
+                "CONNECTED".to_string(),
+            )
+            .await;
+            return Ok(true);
+        }
+        slots::release(seat, &player.id).await?;
+    }
+    Ok(false)
+}
+
+async fn close_table(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+) -> Result<(), Error> {
+    let mode = match admin.role.and_then(game::for_role) {
+        Some(mode) => mode,
+        None => return Ok(()),
+    };
+    let role = mode.player_role();
+
+    mode.on_close(request_context.clone(), admin).await?;
+    for player in find_players(admin).await? {
+        slots::vacate(&player).await?;
+        return_to_que(player.id.clone(), role).await;
+        send::table_closed(request_context.clone(), &player).await;
+    }
+
+    for other in find_admins(role).await? {
+        if other.id != admin.id {
+            fill_table(request_context.clone(), &other).await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,455 @@
+use crate::config;
+use crate::connection_operations::*;
+use crate::error::Error;
+use crate::models::*;
+use crate::send;
+use crate::store;
+use crate::time;
+use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequestContext;
+use dynomite::attr_map;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const KIND: &str = "quiz";
+const ANSWER_KIND: &str = "quiz_answer";
+const ANSWERS_KIND: &str = "quiz_answers";
+const OPEN_KIND: &str = "quiz_open";
+const ANSWER_RETENTION: u64 = 24 * 60 * 60;
+const MAX_OPTIONS: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Question {
+    pub text: String,
+    pub options: Vec<String>,
+    pub answer: usize,
+    pub duration: Option<u64>,
+    pub points: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct QuestionSet {
+    pub title: Option<String>,
+    pub questions: Vec<Question>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Standing {
+    pub connection_id: String,
+    pub nickname: Option<String>,
+    pub score: i64,
+    pub correct: i64,
+}
+
+pub fn parse(format: &str, body: &str) -> Result<QuestionSet, String> {
+    let set: QuestionSet = match format {
+        "json" => serde_json::from_str(body).map_err(|err| err.to_string())?,
+        "toml" => toml::from_str(body).map_err(|err| err.to_string())?,
+        _ => return Err(format!("Unknown question set format {}", format)),
+    };
+
+    if set.questions.is_empty() {
+        return Err("Question set is empty".to_string());
+    }
+    for (index, question) in set.questions.iter().enumerate() {
+        if question.text.trim().is_empty() {
+            return Err(format!("Question {} has no text", index + 1));
+        }
+        if !(2..=MAX_OPTIONS).contains(&question.options.len()) {
+            return Err(format!(
+                "Question {} needs between 2 and 10 options",
+                index + 1
+            ));
+        }
+        if question.answer >= question.options.len() {
+            return Err(format!("Question {} has no such answer", index + 1));
+        }
+        if question.duration == Some(0) {
+            return Err(format!("Question {} has no time to answer", index + 1));
+        }
+    }
+    Ok(set)
+}
+
+pub fn score(points: i64, duration: u64, elapsed: u64) -> i64 {
+    if duration == 0 || elapsed >= duration {
+        return 0;
+    }
+    let remaining = (duration - elapsed) as f64 / duration as f64;
+    (points as f64 * (0.5 + 0.5 * remaining)).round() as i64
+}
+
+pub fn standings(answers: &[QuizAnswer]) -> Vec<Standing> {
+    let mut standings: Vec<Standing> = Vec::new();
+    let mut positions: HashMap<&str, usize> = HashMap::new();
+    for answer in answers {
+        let position = *positions.entry(&answer.connection_id).or_insert_with(|| {
+            standings.push(Standing {
+                connection_id: answer.connection_id.clone(),
+                nickname: answer.nickname.clone(),
+                score: 0,
+                correct: 0,
+            });
+            standings.len() - 1
+        });
+        let standing = &mut standings[position];
+        standing.score += answer.points;
+        if answer.points > 0 {
+            standing.correct += 1;
+        }
+    }
+
+    standings.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then(b.correct.cmp(&a.correct))
+            .then(a.connection_id.cmp(&b.connection_id))
+    });
+    standings
+}
+
+fn quiz_id(table_id: &str) -> String {
+    format!("{}#{}", KIND, table_id)
+}
+
+fn answers_id(run: &str) -> String {
+    format!("{}#{}", ANSWERS_KIND, run)
+}
+
+fn questions(quiz: &Quiz) -> Result<Vec<Question>, Error> {
+    Ok(serde_json::from_str(&quiz.questions)?)
+}
+
+pub async fn load(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+    format: &str,
+    body: &str,
+) -> Result<(), Error> {
+    let set = parse(format, body)?;
+    let quiz = Quiz {
+        id: quiz_id(&admin.id),
+        kind: KIND.to_string(),
+        table_id: admin.id.clone(),
+        run: hex::encode(rand::thread_rng().gen::<[u8; 8]>()),
+        title: set.title,
+        questions: serde_json::to_string(&set.questions)?,
+        current: None,
+        asked_at: 0,
+        deadline: 0,
+        round_open: false,
+    };
+    store::put_record(quiz.clone()).await?;
+    send::quiz_loaded(
+        request_context,
+        admin.id.clone(),
+        &quiz,
+        set.questions.len(),
+    )
+    .await;
+    Ok(())
+}
+
+pub async fn next(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+) -> Result<(), Error> {
+    let quiz: Option<Quiz> = store::get_record(quiz_id(&admin.id)).await?;
+    let mut quiz = quiz.ok_or("No quiz loaded")?;
+    if quiz.round_open {
+        close_round(request_context.clone(), &mut quiz).await?;
+    }
+
+    let questions = questions(&quiz)?;
+    let index = quiz.current.map_or(0, |current| current as usize + 1);
+    let question = match questions.get(index) {
+        Some(question) => question,
+        None => return stop(request_context, admin).await,
+    };
+
+    let duration = question.duration.unwrap_or_else(config::quiz_duration);
+    quiz.current = Some(index as i64);
+    quiz.asked_at = time::now_millis();
+    quiz.deadline = quiz.asked_at + duration * 1000;
+    quiz.round_open = true;
+    store::put_record(quiz.clone()).await?;
+    store::add_to_set(
+        OPEN_KIND.to_string(),
+        "members",
+        quiz.id.clone(),
+        attr_map!("kind" => OPEN_KIND.to_string()),
+    )
+    .await?;
+
+    for recipient in recipients(admin).await? {
+        send::quiz_question(request_context.clone(), recipient, &quiz, question).await;
+    }
+    Ok(())
+}
+
+pub async fn stop(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+) -> Result<(), Error> {
+    let quiz: Option<Quiz> = store::get_record(quiz_id(&admin.id)).await?;
+    let quiz = match quiz {
+        Some(quiz) => quiz,
+        None => return Ok(()),
+    };
+
+    let standings = standings(&answers(&quiz.run).await?);
+    store::delete_record(quiz.id.clone()).await?;
+    store::remove_from_set(OPEN_KIND.to_string(), "members", quiz.id.clone()).await?;
+    for recipient in recipients(admin).await? {
+        send::quiz_finished(request_context.clone(), recipient, &quiz, &standings).await;
+    }
+    Ok(())
+}
+
+pub async fn answer(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection: &Connection,
+    question: i64,
+    choice: usize,
+) -> Result<(), Error> {
+    let admin = find_display_admin(connection).await?;
+    let quiz: Option<Quiz> = store::get_record(quiz_id(&admin.id)).await?;
+    let mut quiz = match quiz {
+        Some(quiz) => quiz,
+        None => {
+            send::quiz_rejected(request_context, "no_quiz").await;
+            return Ok(());
+        }
+    };
+
+    let now = time::now_millis();
+    if !quiz.round_open || quiz.current != Some(question) || now >= quiz.deadline {
+        if quiz.round_open && now >= quiz.deadline {
+            close_round(request_context.clone(), &mut quiz).await?;
+        }
+        send::quiz_rejected(request_context, "question_closed").await;
+        return Ok(());
+    }
+    let questions = questions(&quiz)?;
+    let asked = &questions[question as usize];
+    if choice >= asked.options.len() {
+        send::quiz_rejected(request_context, "invalid_input").await;
+        return Ok(());
+    }
+
+    let points = if choice == asked.answer {
+        score(
+            asked.points.unwrap_or_else(config::quiz_points),
+            quiz.deadline - quiz.asked_at,
+            now - quiz.asked_at,
+        )
+    } else {
+        0
+    };
+    let id = format!(
+        "{}#{}#{}#{}",
+        ANSWER_KIND, quiz.run, question, connection.id
+    );
+    let expires_at = time::now_seconds() + ANSWER_RETENTION;
+    let first_answer = store::create_record(QuizAnswer {
+        id: id.clone(),
+        kind: ANSWER_KIND.to_string(),
+        run: quiz.run.clone(),
+        question,
+        connection_id: connection.id.clone(),
+        nickname: connection.nickname.clone(),
+        choice: choice as i64,
+        points,
+        answered_at: now,
+        expires_at: Some(expires_at),
+    })
+    .await?;
+    if !first_answer {
+        send::quiz_rejected(request_context, "already_answered").await;
+        return Ok(());
+    }
+    store::add_to_set(
+        answers_id(&quiz.run),
+        "members",
+        id,
+        attr_map!("kind" => ANSWERS_KIND.to_string(), "clearAt" => expires_at),
+    )
+    .await?;
+
+    send::quiz_answered(request_context, question).await;
+    Ok(())
+}
+
+pub async fn close_due(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+) -> Result<(), Error> {
+    let open: Option<MemberSet> = store::get_record(OPEN_KIND.to_string()).await?;
+    let ids: Vec<String> = open
+        .and_then(|open| open.members)
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    let quizzes: HashMap<String, Quiz> = store::get_records::<Quiz>(ids.clone())
+        .await?
+        .into_iter()
+        .map(|quiz| (quiz.id.clone(), quiz))
+        .collect();
+
+    let now = time::now_millis();
+    for id in ids {
+        match quizzes.get(&id) {
+            Some(quiz) if quiz.round_open => {
+                if quiz.deadline <= now {
+                    close_round(request_context.clone(), &mut quiz.clone()).await?;
+                }
+            }
+            _ => store::remove_from_set(OPEN_KIND.to_string(), "members", id).await?,
+        }
+    }
+    Ok(())
+}
+
+// Only the caller that actually closes the round announces it, so a round closed by the
+// scheduler, a late answer and the admin at the same time is broadcast once.
+async fn close_round(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    quiz: &mut Quiz,
+) -> Result<(), Error> {
+    let index = quiz.current.unwrap_or_default();
+    quiz.round_open = false;
+    let closed = store::update_record_if(
+        quiz.id.clone(),
+        attr_map!("round_open" => false),
+        attr_map!("round_open" => true, "current" => index),
+    )
+    .await?;
+    if !closed {
+        return Ok(());
+    }
+    store::remove_from_set(OPEN_KIND.to_string(), "members", quiz.id.clone()).await?;
+
+    let questions = questions(quiz)?;
+    let question = &questions[index as usize];
+    let mut standings = standings(&answers(&quiz.run).await?);
+    standings.truncate(config::leaderboard_size());
+
+    let admin = find_connection_in_db(UnresolvedConnection {
+        id: quiz.table_id.clone(),
+    })
+    .await;
+    if let Ok(admin) = admin {
+        for recipient in recipients(&admin).await? {
+            send::quiz_round(
+                request_context.clone(),
+                recipient,
+                quiz,
+                question,
+                &standings,
+            )
+            .await;
+        }
+    }
+    Ok(())
+}
+
+async fn answers(run: &str) -> Result<Vec<QuizAnswer>, Error> {
+    let answers: Option<MemberSet> = store::get_record(answers_id(run)).await?;
+    let ids = answers
+        .and_then(|answers| answers.members)
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    store::get_records(ids).await
+}
+
+async fn recipients(admin: &Connection) -> Result<Vec<String>, Error> {
+    let mut recipients: Vec<String> = find_audience(admin, Audience::Observers)
+        .await?
+        .into_iter()
+        .map(|observer| observer.id)
+        .collect();
+    recipients.push(admin.id.clone());
+    Ok(recipients)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer(connection_id: &str, points: i64) -> QuizAnswer {
+        QuizAnswer {
+            connection_id: connection_id.to_string(),
+            points,
+            ..QuizAnswer::default()
+        }
+    }
+
+    #[test]
+    fn parses_json_and_toml() {
+        let json = r#"{"title":"Capitals","questions":[{"text":"France?","options":["Paris","Rome"],"answer":0}]}"#;
+        let toml = r#"
+            title = "Capitals"
+
+            [[questions]]
+            text = "France?"
+            options = ["Paris", "Rome"]
+            answer = 0
+        "#;
+
+        let from_json = parse("json", json).unwrap();
+        assert_eq!(from_json, parse("toml", toml).unwrap());
+        assert_eq!(from_json.title.as_deref(), Some("Capitals"));
+        assert_eq!(from_json.questions[0].options.len(), 2);
+    }
+
+    #[test]
+    fn rejects_broken_question_sets() {
+        let question = |options: &str, answer: usize, duration: &str| {
+            format!(
+                r#"{{"questions":[{{"text":"Q","options":{},"answer":{}{}}}]}}"#,
+                options, answer, duration
+            )
+        };
+
+        assert!(parse("yaml", "").is_err());
+        assert!(parse("json", "{").is_err());
+        assert!(parse("json", r#"{"questions":[]}"#).is_err());
+        assert!(parse("json", &question(r#"["a"]"#, 0, "")).is_err());
+        assert!(parse("json", &question(r#"["a","b"]"#, 2, "")).is_err());
+        assert!(parse("json", &question(r#"["a","b"]"#, 1, r#","duration":0"#)).is_err());
+        assert!(parse("json", &question(r#"["a","b"]"#, 1, r#","duration":5"#)).is_ok());
+    }
+
+    #[test]
+    fn faster_answers_score_more() {
+        assert_eq!(score(1000, 20_000, 0), 1000);
+        assert_eq!(score(1000, 20_000, 10_000), 750);
+        assert_eq!(score(1000, 20_000, 20_000), 0);
+        assert_eq!(score(1000, 0, 0), 0);
+    }
+
+    #[test]
+    fn standings_rank_by_score_then_correct_answers() {
+        let answers = vec![
+            answer("a", 500),
+            answer("b", 900),
+            answer("a", 400),
+            answer("c", 900),
+            answer("c", 0),
+            answer("b", 0),
+        ];
+
+        let standings = standings(&answers);
+        let order: Vec<(&str, i64, i64)> = standings
+            .iter()
+            .map(|standing| {
+                (
+                    standing.connection_id.as_str(),
+                    standing.score,
+                    standing.correct,
+                )
+            })
+            .collect();
+        assert_eq!(order, vec![("a", 900, 2), ("b", 900, 1), ("c", 900, 1)]);
+    }
+}
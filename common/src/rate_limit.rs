@@ -0,0 +1,193 @@
+use crate::config;
+use crate::connection_operations::*;
+use crate::error::Error;
+use crate::models::*;
+use crate::send;
+use crate::time;
+use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequestContext;
+use serde::Deserialize;
+use std::time::Duration;
+
+const MAX_COALESCE_WAIT: u64 = 1000;
+const MAX_ATTEMPTS: usize = 3;
+
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Overflow {
+    Drop,
+    Coalesce,
+}
+
+#[derive(Debug, Deserialize, Copy, Clone)]
+#[serde(default)]
+pub struct RateLimit {
+    pub messages: f64,
+    pub messages_per_second: f64,
+    pub bytes: f64,
+    pub bytes_per_second: f64,
+    pub overflow: Overflow,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit {
+            messages: 20.0,
+            messages_per_second: 10.0,
+            bytes: 16384.0,
+            bytes_per_second: 8192.0,
+            overflow: Overflow::Drop,
+        }
+    }
+}
+
+pub enum Admission {
+    Allowed,
+    Limited { retry_after: u64 },
+}
+
+fn refill(connection: &Connection, limit: &RateLimit, now: u64) -> (f64, f64) {
+    let elapsed = connection
+        .refilled_at
+        .map(|refilled_at| now.saturating_sub(refilled_at) as f64 / 1000.0)
+        .unwrap_or(0.0);
+    let tokens = connection.tokens.unwrap_or(limit.messages) + elapsed * limit.messages_per_second;
+    let byte_tokens =
+        connection.byte_tokens.unwrap_or(limit.bytes) + elapsed * limit.bytes_per_second;
+    (tokens.min(limit.messages), byte_tokens.min(limit.bytes))
+}
+
+fn retry_after(missing: f64, per_second: f64) -> u64 {
+    if missing <= 0.0 {
+        0
+    } else if per_second <= 0.0 {
+        u64::MAX
+    } else {
+        (missing / per_second * 1000.0).ceil() as u64
+    }
+}
+
+pub async fn admit(
+    connection: &Connection,
+    limit: &RateLimit,
+    bytes: usize,
+) -> Result<Admission, Error> {
+    let bytes = bytes as f64;
+    let mut connection = connection.clone();
+
+    for _ in 0..MAX_ATTEMPTS {
+        let now = time::now_millis();
+        let (tokens, byte_tokens) = refill(&connection, limit, now);
+
+        if tokens < 1.0 || byte_tokens < bytes {
+            let wait = retry_after(1.0 - tokens, limit.messages_per_second)
+                .max(retry_after(bytes - byte_tokens, limit.bytes_per_second));
+            return Ok(Admission::Limited { retry_after: wait });
+        }
+
+        let updated = update_buckets(
+            connection.id.clone(),
+            tokens - 1.0,
+            byte_tokens - bytes,
+            now,
+            connection.refilled_at,
+        )
+        .await?;
+        if updated {
+            return Ok(Admission::Allowed);
+        }
+
+        connection = find_connection_in_db(UnresolvedConnection {
+            id: connection.id.clone(),
+        })
+        .await?;
+    }
+
+    Ok(Admission::Limited { retry_after: 0 })
+}
+
+pub async fn check(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection: &Connection,
+    bytes: usize,
+) -> Result<bool, Error> {
+    let limit = config::rate_limit(connection.role.unwrap_or(Role::Observer));
+
+    let retry_after = match admit(connection, &limit, bytes).await? {
+        Admission::Allowed => return Ok(true),
+        Admission::Limited { retry_after } => retry_after,
+    };
+
+    if limit.overflow == Overflow::Coalesce && retry_after <= MAX_COALESCE_WAIT {
+        let seq = bump_input_seq(connection.id.clone()).await?;
+        tokio::time::delay_for(Duration::from_millis(retry_after)).await;
+
+        let latest = find_connection_in_db(UnresolvedConnection {
+            id: connection.id.clone(),
+        })
+        .await?;
+        if latest.input_seq != Some(seq) {
+            return Ok(false);
+        }
+        if let Admission::Allowed = admit(&latest, &limit, bytes).await? {
+            return Ok(true);
+        }
+    }
+
+    send::rate_limited(request_context, retry_after).await;
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit() -> RateLimit {
+        RateLimit {
+            messages: 10.0,
+            messages_per_second: 5.0,
+            bytes: 1000.0,
+            bytes_per_second: 500.0,
+            overflow: Overflow::Drop,
+        }
+    }
+
+    fn drained(refilled_at: u64) -> Connection {
+        Connection {
+            tokens: Some(0.0),
+            byte_tokens: Some(0.0),
+            refilled_at: Some(refilled_at),
+            ..Connection::default()
+        }
+    }
+
+    #[test]
+    fn new_connections_start_full() {
+        assert_eq!(
+            refill(&Connection::default(), &limit(), 5_000),
+            (10.0, 1000.0)
+        );
+    }
+
+    #[test]
+    fn refill_adds_tokens_for_elapsed_time() {
+        assert_eq!(refill(&drained(1_000), &limit(), 1_500), (2.5, 250.0));
+    }
+
+    #[test]
+    fn refill_stops_at_bucket_size() {
+        assert_eq!(refill(&drained(1_000), &limit(), 60_000), (10.0, 1000.0));
+    }
+
+    #[test]
+    fn refill_ignores_clock_skew() {
+        assert_eq!(refill(&drained(2_000), &limit(), 1_000), (0.0, 0.0));
+    }
+
+    #[test]
+    fn retry_after_waits_for_missing_tokens() {
+        assert_eq!(retry_after(0.0, 5.0), 0);
+        assert_eq!(retry_after(1.0, 5.0), 200);
+        assert_eq!(retry_after(0.5, 3.0), 167);
+        assert_eq!(retry_after(1.0, 0.0), u64::MAX);
+    }
+}
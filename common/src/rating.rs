@@ -0,0 +1,38 @@
+pub fn expected_score(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+pub fn update(winner: f64, loser: f64, k: f64) -> (f64, f64) {
+    let change = k * (1.0 - expected_score(winner, loser));
+    (winner + change, loser - change)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_ratings_expect_a_draw() {
+        assert_eq!(expected_score(1200.0, 1200.0), 0.5);
+    }
+
+    #[test]
+    fn expected_scores_are_complementary() {
+        let favourite = expected_score(1400.0, 1200.0);
+        let underdog = expected_score(1200.0, 1400.0);
+        assert!(favourite > 0.75 && favourite < 0.77);
+        assert!((favourite + underdog - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn update_moves_points_from_loser_to_winner() {
+        assert_eq!(update(1200.0, 1200.0, 32.0), (1216.0, 1184.0));
+    }
+
+    #[test]
+    fn upset_moves_more_points_than_expected_win() {
+        let (favourite, _) = update(1400.0, 1200.0, 32.0);
+        let (underdog, _) = update(1200.0, 1400.0, 32.0);
+        assert!(underdog - 1200.0 > favourite - 1400.0);
+    }
+}
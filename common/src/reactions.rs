@@ -0,0 +1,51 @@
+use crate::aggregate;
+use crate::config;
+use crate::connection_operations::*;
+use crate::error::Error;
+use crate::models::*;
+use crate::send;
+use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequestContext;
+
+const KIND: &str = "reaction";
+
+pub async fn react(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection: &Connection,
+    reaction: String,
+) -> Result<(), Error> {
+    if !config::reactions().contains(&reaction) {
+        send::vote_rejected(request_context, "invalid_input").await;
+        return Ok(());
+    }
+
+    let display = find_display_admin(connection).await?;
+    let tick = config::reaction_window();
+    let window = aggregate::current_window(tick);
+    aggregate::add(KIND, &display.id, window, reaction).await?;
+    let (batches, _) = aggregate::flush_table(KIND, &display.id, tick).await?;
+    send_batches(request_context, batches).await;
+    Ok(())
+}
+
+pub async fn flush_due(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+) -> Result<(), Error> {
+    let batches = aggregate::flush_due(KIND, config::reaction_window()).await?;
+    send_batches(request_context, batches).await;
+    Ok(())
+}
+
+async fn send_batches(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    batches: Vec<aggregate::Batch>,
+) {
+    for batch in batches {
+        send::reactions(
+            request_context.clone(),
+            batch.table_id,
+            batch.window,
+            &batch.counts,
+        )
+        .await;
+    }
+}
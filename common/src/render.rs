@@ -0,0 +1,297 @@
+use crate::bracket::{Bracket, Format};
+use crate::canvas::Canvas;
+use crate::error::Error;
+use crate::models::PlayerStats;
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+const TEXT_SCALE: usize = 2;
+const MARGIN: usize = 16;
+const MAX_LINE_LENGTH: usize = 48;
+const BACKGROUND: [u8; 3] = [255, 255, 255];
+const INK: [u8; 3] = [34, 34, 34];
+
+struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    fn new(width: usize, height: usize) -> Image {
+        Image {
+            width,
+            height,
+            pixels: BACKGROUND.repeat(width * height),
+        }
+    }
+
+    fn fill(&mut self, x: usize, y: usize, size: usize, color: [u8; 3]) {
+        for row in y..(y + size).min(self.height) {
+            for column in x..(x + size).min(self.width) {
+                let offset = (row * self.width + column) * 3;
+                self.pixels[offset..offset + 3].copy_from_slice(&color);
+            }
+        }
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, self.width as u32, self.height as u32);
+            encoder.set_color(png::ColorType::RGB);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header()?;
+            writer.write_image_data(&self.pixels)?;
+        }
+        Ok(bytes)
+    }
+}
+
+pub fn canvas(canvas: &Canvas, scale: usize) -> Result<Vec<u8>, Error> {
+    let scale = scale.max(1);
+    let palette: Vec<[u8; 3]> = canvas
+        .palette
+        .iter()
+        .map(|color| parse_color(color))
+        .collect();
+    let mut image = Image::new(
+        canvas.width as usize * scale,
+        canvas.height as usize * scale,
+    );
+
+    for (index, color) in canvas.pixels.iter().enumerate() {
+        let x = index % canvas.width as usize;
+        let y = index / canvas.width as usize;
+        let color = palette.get(*color as usize).copied().unwrap_or(INK);
+        image.fill(x * scale, y * scale, scale, color);
+    }
+    image.encode()
+}
+
+pub fn leaderboard(stats: &[PlayerStats]) -> Result<Vec<u8>, Error> {
+    let mut lines = vec!["LEADERBOARD".to_string(), String::new()];
+    lines.extend(stats.iter().enumerate().map(|(place, stats)| {
+        let rating = stats
+            .rating
+            .map(|rating| format!(" R {:.0}", rating))
+            .unwrap_or_default();
+        format!(
+            "{}. {} W {} L {}{}",
+            place + 1,
+            stats.nickname,
+            stats.wins,
+            stats.losses,
+            rating
+        )
+    }));
+    text(&lines)
+}
+
+pub fn bracket(bracket: &Bracket) -> Result<Vec<u8>, Error> {
+    let name = |id: &str| {
+        bracket
+            .entrants
+            .iter()
+            .find(|entrant| entrant.id == id)
+            .and_then(|entrant| entrant.nickname.clone())
+            .unwrap_or_else(|| id.chars().take(8).collect())
+    };
+    let slot = |round: usize, player: &Option<String>| match player {
+        Some(player) => name(player),
+        None if round == 0 => "BYE".to_string(),
+        None => "TBD".to_string(),
+    };
+
+    let mut lines = vec![match bracket.format {
+        Format::SingleElimination => "SINGLE ELIMINATION".to_string(),
+        Format::RoundRobin => "ROUND ROBIN".to_string(),
+    }];
+    let mut round = None;
+    for game in &bracket.matches {
+        if round != Some(game.round) {
+            round = Some(game.round);
+            lines.push(String::new());
+            lines.push(format!("ROUND {}", game.round + 1));
+        }
+        let result = game
+            .winner
+            .as_ref()
+            .map(|winner| format!(" > {}", name(winner)))
+            .unwrap_or_default();
+        lines.push(format!(
+            "{} VS {}{}",
+            slot(game.round, &game.players[0]),
+            slot(game.round, &game.players[1]),
+            result
+        ));
+    }
+    if let Some(champion) = &bracket.champion {
+        lines.push(String::new());
+        lines.push(format!("CHAMPION: {}", name(champion)));
+    }
+    text(&lines)
+}
+
+pub fn text(lines: &[String]) -> Result<Vec<u8>, Error> {
+    let lines: Vec<Vec<char>> = lines
+        .iter()
+        .map(|line| line.to_uppercase().chars().take(MAX_LINE_LENGTH).collect())
+        .collect();
+    let columns = lines
+        .iter()
+        .map(|line| line.len())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let cell_width = (GLYPH_WIDTH + 1) * TEXT_SCALE;
+    let cell_height = (GLYPH_HEIGHT + 2) * TEXT_SCALE;
+    let mut image = Image::new(
+        columns * cell_width + MARGIN * 2,
+        lines.len().max(1) * cell_height + MARGIN * 2,
+    );
+
+    for (row, line) in lines.iter().enumerate() {
+        for (column, character) in line.iter().enumerate() {
+            let left = MARGIN + column * cell_width;
+            let top = MARGIN + row * cell_height;
+            for (y, bits) in glyph(*character).iter().enumerate() {
+                for x in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - x)) != 0 {
+                        image.fill(left + x * TEXT_SCALE, top + y * TEXT_SCALE, TEXT_SCALE, INK);
+                    }
+                }
+            }
+        }
+    }
+    image.encode()
+}
+
+fn parse_color(color: &str) -> [u8; 3] {
+    let hex = color.trim_start_matches('#');
+    match hex::decode(hex) {
+        Ok(bytes) if bytes.len() == 3 => [bytes[0], bytes[1], bytes[2]],
+        _ => INK,
+    }
+}
+
+fn glyph(character: char) -> [u8; GLYPH_HEIGHT] {
+    match character {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        ' ' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '>' => [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    fn size(png: &[u8]) -> (u32, u32) {
+        let word = |at: usize| u32::from_be_bytes([png[at], png[at + 1], png[at + 2], png[at + 3]]);
+        (word(16), word(20))
+    }
+
+    #[test]
+    fn canvas_is_scaled() {
+        let canvas = Canvas {
+            width: 3,
+            height: 2,
+            palette: vec!["#ffffff".to_string(), "#ff0000".to_string()],
+            pixels: vec![0, 1, 0, 1, 0, 1],
+        };
+        let png = super::canvas(&canvas, 4).unwrap();
+        assert!(png.starts_with(SIGNATURE));
+        assert_eq!(size(&png), (12, 8));
+    }
+
+    #[test]
+    fn zero_scale_renders_one_pixel_per_cell() {
+        let canvas = Canvas {
+            width: 2,
+            height: 2,
+            palette: vec!["not a color".to_string()],
+            pixels: vec![0, 0, 0, 5],
+        };
+        assert_eq!(size(&super::canvas(&canvas, 0).unwrap()), (2, 2));
+    }
+
+    #[test]
+    fn text_fits_the_longest_line() {
+        let png = text(&["ab".to_string(), "abcd".to_string()]).unwrap();
+        assert!(png.starts_with(SIGNATURE));
+        let cell_width = (GLYPH_WIDTH + 1) * TEXT_SCALE;
+        let cell_height = (GLYPH_HEIGHT + 2) * TEXT_SCALE;
+        assert_eq!(
+            size(&png),
+            (
+                (4 * cell_width + MARGIN * 2) as u32,
+                (2 * cell_height + MARGIN * 2) as u32
+            )
+        );
+    }
+
+    #[test]
+    fn long_lines_are_truncated() {
+        let png = text(&["x".repeat(MAX_LINE_LENGTH * 2)]).unwrap();
+        let cell_width = (GLYPH_WIDTH + 1) * TEXT_SCALE;
+        assert_eq!(
+            size(&png).0,
+            (MAX_LINE_LENGTH * cell_width + MARGIN * 2) as u32
+        );
+    }
+
+    #[test]
+    fn empty_text_still_renders() {
+        let png = text(&[]).unwrap();
+        assert!(png.starts_with(SIGNATURE));
+        assert!(size(&png).0 > 0 && size(&png).1 > 0);
+    }
+}
@@ -0,0 +1,404 @@
+use crate::error::Error;
+use crate::game::{self, GameMode, QueuePolicy};
+use crate::models::Role;
+use crate::permissions;
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+
+pub const BUILT_IN: &[Role] = &[
+    Role::Observer,
+    Role::PlayerPong,
+    Role::PlayerDisplay,
+    Role::AdminPong,
+    Role::AdminDisplay,
+];
+
+pub const ROUTES: &[&str] = &[
+    "selection",
+    "leave_queue",
+    "release_role",
+    "switch_role",
+    "ready",
+    "upstream",
+    "downstream",
+    "snapshot",
+    "ban",
+    "unban",
+    "bans",
+    "que",
+    "match_result",
+    "tournament_start",
+    "tournament_cancel",
+    "bracket",
+    "crowd",
+    "submissions",
+    "moderate",
+    "render",
+    "quiz_load",
+    "quiz_next",
+    "quiz_stop",
+    "leaderboard",
+    "vote",
+    "polls",
+    "reaction",
+    "submit",
+    "pixel",
+    "canvas",
+    "answer",
+];
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RoleDefinition {
+    pub name: String,
+    pub admin: Option<String>,
+    pub capacity: Option<i64>,
+    pub queue: Option<QueuePolicy>,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RoleFile {
+    #[serde(default)]
+    roles: Vec<RoleDefinition>,
+}
+
+struct Definition {
+    name: &'static str,
+    permissions: Vec<String>,
+}
+
+pub struct Registry {
+    definitions: Vec<Definition>,
+    modes: Vec<&'static dyn GameMode>,
+}
+
+struct ConfiguredMode {
+    name: &'static str,
+    player_role: Role,
+    admin_role: Role,
+    capacity: i64,
+    queue_policy: QueuePolicy,
+}
+
+impl GameMode for ConfiguredMode {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn player_role(&self) -> Role {
+        self.player_role
+    }
+
+    fn admin_role(&self) -> Role {
+        self.admin_role
+    }
+
+    fn capacity(&self) -> i64 {
+        self.capacity
+    }
+
+    fn queue_policy(&self) -> QueuePolicy {
+        self.queue_policy
+    }
+}
+
+static REGISTRY: OnceCell<Registry> = OnceCell::new();
+
+pub fn init() -> Result<(), Error> {
+    REGISTRY.get_or_try_init(load)?;
+    permissions::init()
+}
+
+fn registry() -> &'static Registry {
+    REGISTRY
+        .get_or_init(|| load().unwrap_or_else(|err| panic!("Invalid role definitions: {}", err)))
+}
+
+pub fn modes() -> &'static [&'static dyn GameMode] {
+    &registry().modes
+}
+
+pub fn find(name: &str) -> Option<Role> {
+    BUILT_IN
+        .iter()
+        .copied()
+        .find(|role| role.name() == name)
+        .or_else(|| {
+            registry()
+                .definitions
+                .iter()
+                .find(|definition| definition.name == name)
+                .map(|definition| Role::Custom(definition.name))
+        })
+        .or_else(|| {
+            modes()
+                .iter()
+                .flat_map(|mode| vec![mode.player_role(), mode.admin_role()])
+                .find(|role| role.name() == name)
+        })
+}
+
+pub fn permissions(role: Role) -> &'static [String] {
+    registry()
+        .definitions
+        .iter()
+        .find(|definition| Role::Custom(definition.name) == role)
+        .map(|definition| definition.permissions.as_slice())
+        .unwrap_or_default()
+}
+
+// roleDefinitions holds either a path to a TOML or JSON file packaged with the function,
+// or the JSON definitions themselves.
+fn load() -> Result<Registry, Error> {
+    let source = env::var("roleDefinitions").unwrap_or_default();
+    let source = source.trim();
+    let definitions = if source.is_empty() {
+        Vec::new()
+    } else if source.starts_with('{') {
+        parse("roleDefinitions.json", source)?
+    } else {
+        let contents = fs::read_to_string(source)
+            .map_err(|err| format!("Cannot read role definitions {}: {}", source, err))?;
+        parse(source, &contents)?
+    };
+    build(definitions).map_err(|err| err.into())
+}
+
+pub fn parse(path: &str, contents: &str) -> Result<Vec<RoleDefinition>, String> {
+    let file: RoleFile = if path.ends_with(".json") {
+        serde_json::from_str(contents).map_err(|err| format!("{}: {}", path, err))?
+    } else {
+        toml::from_str(contents).map_err(|err| format!("{}: {}", path, err))?
+    };
+    validate(&file.roles)?;
+    Ok(file.roles)
+}
+
+fn reserved(name: &str) -> bool {
+    BUILT_IN.iter().any(|role| role.name() == name)
+        || game::built_in()
+            .iter()
+            .any(|mode| mode.player_role().name() == name || mode.admin_role().name() == name)
+}
+
+pub fn validate(definitions: &[RoleDefinition]) -> Result<(), String> {
+    let defined = |name: &str| definitions.iter().filter(|role| role.name == name).count();
+
+    for role in definitions {
+        if role.name.is_empty() || !role.name.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!(
+                "Role name {:?} must be alphanumeric and not empty",
+                role.name
+            ));
+        }
+        if reserved(&role.name) {
+            return Err(format!(
+                "Role {} is built in and cannot be redefined",
+                role.name
+            ));
+        }
+        if defined(&role.name) > 1 {
+            return Err(format!("Role {} is defined more than once", role.name));
+        }
+        if let Some(permission) = role
+            .permissions
+            .iter()
+            .find(|permission| !ROUTES.contains(&permission.as_str()))
+        {
+            return Err(format!(
+                "Role {} has unknown permission {}",
+                role.name, permission
+            ));
+        }
+
+        let admin = match &role.admin {
+            Some(admin) => admin,
+            None => {
+                if role.capacity.is_some() || role.queue.is_some() {
+                    return Err(format!(
+                        "Role {} has a capacity or queue but no admin role",
+                        role.name
+                    ));
+                }
+                continue;
+            }
+        };
+        if admin == &role.name {
+            return Err(format!("Role {} cannot be its own admin", role.name));
+        }
+        let admin_definition = definitions
+            .iter()
+            .find(|definition| &definition.name == admin)
+            .ok_or_else(|| format!("Role {} pairs with unknown admin role {}", role.name, admin))?;
+        if admin_definition.admin.is_some() {
+            return Err(format!(
+                "Role {} pairs with {}, which is a player role",
+                role.name, admin
+            ));
+        }
+        if let Some(other) = definitions
+            .iter()
+            .find(|other| other.name != role.name && other.admin.as_ref() == Some(admin))
+        {
+            return Err(format!(
+                "Admin role {} is paired with both {} and {}",
+                admin, role.name, other.name
+            ));
+        }
+        if role.capacity.map_or(false, |capacity| capacity < 1) {
+            return Err(format!("Role {} needs a capacity of at least 1", role.name));
+        }
+    }
+    Ok(())
+}
+
+fn build(definitions: Vec<RoleDefinition>) -> Result<Registry, String> {
+    let names: Vec<&'static str> = definitions
+        .iter()
+        .map(|definition| &*Box::leak(definition.name.clone().into_boxed_str()))
+        .collect();
+    let name_of = |name: &str| {
+        names
+            .iter()
+            .copied()
+            .find(|defined| *defined == name)
+            .ok_or_else(|| format!("Unknown role {}", name))
+    };
+
+    let mut modes: Vec<&'static dyn GameMode> = game::built_in().to_vec();
+    for definition in &definitions {
+        if let Some(admin) = &definition.admin {
+            let name = name_of(&definition.name)?;
+            modes.push(Box::leak(Box::new(ConfiguredMode {
+                name,
+                player_role: Role::Custom(name),
+                admin_role: Role::Custom(name_of(admin)?),
+                capacity: definition.capacity.unwrap_or(1),
+                queue_policy: definition.queue.unwrap_or(QueuePolicy::Arrival),
+            })));
+        }
+    }
+
+    Ok(Registry {
+        definitions: definitions
+            .into_iter()
+            .zip(names)
+            .map(|(definition, name)| Definition {
+                name,
+                permissions: definition.permissions,
+            })
+            .collect(),
+        modes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role(name: &str, admin: Option<&str>) -> RoleDefinition {
+        RoleDefinition {
+            name: name.to_string(),
+            admin: admin.map(str::to_string),
+            capacity: None,
+            queue: None,
+            permissions: Vec::new(),
+        }
+    }
+
+    fn error(definitions: &[RoleDefinition]) -> String {
+        validate(definitions).unwrap_err()
+    }
+
+    #[test]
+    fn accepts_paired_and_permission_roles() {
+        let moderator = RoleDefinition {
+            permissions: vec!["ban".to_string(), "bans".to_string()],
+            ..role("Moderator", None)
+        };
+        let player = RoleDefinition {
+            capacity: Some(8),
+            queue: Some(QueuePolicy::Takeover),
+            ..role("PlayerQuiz", Some("AdminQuiz"))
+        };
+        assert_eq!(
+            validate(&[player, role("AdminQuiz", None), moderator]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_bad_and_reserved_names() {
+        assert!(error(&[role("", None)]).contains("alphanumeric"));
+        assert!(error(&[role("Quiz Master", None)]).contains("alphanumeric"));
+        assert!(error(&[role("Observer", None)]).contains("built in"));
+        assert!(error(&[role("AdminPong", None)]).contains("built in"));
+        assert!(error(&[role("Host", None), role("Host", None)]).contains("more than once"));
+    }
+
+    #[test]
+    fn rejects_unknown_permissions() {
+        let flyer = RoleDefinition {
+            permissions: vec!["fly".to_string()],
+            ..role("Flyer", None)
+        };
+        assert!(error(&[flyer]).contains("unknown permission fly"));
+    }
+
+    #[test]
+    fn rejects_broken_pairings() {
+        assert!(error(&[role("PlayerQuiz", Some("AdminQuiz"))]).contains("unknown admin role"));
+        assert!(error(&[role("Solo", Some("Solo"))]).contains("its own admin"));
+        assert!(error(&[
+            role("PlayerA", Some("PlayerB")),
+            role("PlayerB", Some("Host")),
+            role("Host", None),
+        ])
+        .contains("which is a player role"));
+        assert!(error(&[
+            role("PlayerA", Some("Host")),
+            role("PlayerB", Some("Host")),
+            role("Host", None),
+        ])
+        .contains("paired with both"));
+    }
+
+    #[test]
+    fn capacity_needs_an_admin_and_a_seat() {
+        let unpaired = RoleDefinition {
+            capacity: Some(2),
+            ..role("Player", None)
+        };
+        assert!(error(&[unpaired]).contains("no admin role"));
+
+        let empty = RoleDefinition {
+            capacity: Some(0),
+            ..role("Player", Some("Host"))
+        };
+        assert!(error(&[empty, role("Host", None)]).contains("at least 1"));
+    }
+
+    #[test]
+    fn parses_the_example_file() {
+        let definitions = parse(
+            "roles.example.toml",
+            include_str!("../../roles.example.toml"),
+        )
+        .unwrap();
+        assert_eq!(definitions.len(), 3);
+        assert_eq!(definitions[0].admin.as_deref(), Some("AdminQuiz"));
+    }
+
+    #[test]
+    fn parses_json_definitions() {
+        let definitions = parse(
+            "roleDefinitions.json",
+            r#"{"roles":[{"name":"Moderator","permissions":["bans"]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(definitions[0].permissions, vec!["bans".to_string()]);
+    }
+}
@@ -1,24 +1,38 @@
+use crate::bracket::Bracket;
+use crate::canvas::Canvas;
 use crate::connection_operations::delete_player;
 use crate::error::Error;
+use crate::join;
 use crate::models;
+use crate::quiz::{Question, Standing};
 use aws_lambda_events::event::apigw::{
     ApiGatewayWebsocketProxyRequest, ApiGatewayWebsocketProxyRequestContext,
 };
 use bytes::Bytes;
 use dynomite::dynamodb::{DynamoDb, DynamoDbClient, GetItemInput};
 use dynomite::{FromAttributes, Item};
+use log::debug;
 use rusoto_apigatewaymanagementapi::{
-    ApiGatewayManagementApi, ApiGatewayManagementApiClient, PostToConnectionError,
-    PostToConnectionRequest,
+    ApiGatewayManagementApi, ApiGatewayManagementApiClient, DeleteConnectionRequest,
+    PostToConnectionError, PostToConnectionRequest,
 };
 use rusoto_core::{Region, RusotoError};
 use serde_json::json;
+use std::collections::HashMap;
 use std::env;
 
 thread_local! {
     static DDB: DynamoDbClient = DynamoDbClient::new(Default::default());
 }
 
+pub fn context_from_env() -> ApiGatewayWebsocketProxyRequestContext {
+    ApiGatewayWebsocketProxyRequestContext {
+        domain_name: env::var("websocketDomain").ok(),
+        stage: env::var("stage").ok(),
+        ..ApiGatewayWebsocketProxyRequestContext::default()
+    }
+}
+
 fn endpoint(ctx: &ApiGatewayWebsocketProxyRequestContext) -> String {
     format!(
         "https://{}/{}",
@@ -37,10 +51,8 @@ pub async fn pong(
         .connection_id
         .ok_or("Missing Connection ID")?;
 
-    let connection = models::Connection {
+    let connection = models::UnresolvedConnection {
         id: connection_id.clone(),
-        role: None,
-        que: false,
     };
 
     let res = client
@@ -66,12 +78,66 @@ pub async fn pong(
 
 pub async fn role_accepted(
     request_context: ApiGatewayWebsocketProxyRequestContext,
-    role: models::Role,
+    player: &models::Connection,
 ) {
+    let message = serde_json::to_string(&json!({
+        "role": player.role,
+        "status": "accepted",
+        "slot": player.slot,
+        "table": player.table_id,
+        "nickname": player.nickname
+    }))
+    .unwrap_or_default();
+    send(request_context, player.id.clone(), message).await;
+}
+
+pub async fn your_turn(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    player: &models::Connection,
+    timeout: u64,
+) {
+    let message = serde_json::to_string(&json!({
+        "role": player.role,
+        "status": "your_turn",
+        "slot": player.slot,
+        "table": player.table_id,
+        "timeout": timeout
+    }))
+    .unwrap_or_default();
+    send(request_context, player.id.clone(), message).await;
+}
+
+pub async fn rotated(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    player: &models::Connection,
+) {
+    let message = serde_json::to_string(&json!({ "role": player.role, "status": "rotated" }))
+        .unwrap_or_default();
+    send(request_context, player.id.clone(), message).await;
+}
+
+pub async fn table_closed(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    player: &models::Connection,
+) {
+    let message = serde_json::to_string(&json!({ "role": player.role, "status": "table_closed" }))
+        .unwrap_or_default();
+    send(request_context, player.id.clone(), message).await;
+}
+
+pub async fn skipped(request_context: ApiGatewayWebsocketProxyRequestContext, id: String) {
+    let message =
+        serde_json::to_string(&json!({ "role": models::Role::Observer, "status": "skipped" }))
+            .unwrap_or_default();
+    send(request_context, id, message).await;
+}
+
+pub async fn ready_rejected(request_context: ApiGatewayWebsocketProxyRequestContext, reason: &str) {
     match request_context.clone().connection_id {
         Some(connection_id) => {
-            let message = serde_json::to_string(&json!({ "role": role, "status": "accepted" }))
-                .unwrap_or_default();
+            let message =
+                serde_json::to_string(&json!({ "status": "ready_rejected", "reason": reason }))
+                    .unwrap_or_default();
             send(request_context, connection_id, message).await;
         }
         None => {}
@@ -94,28 +160,605 @@ pub async fn put_in_que(
     }
 }
 
+pub async fn role_released(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    role: models::Role,
+    status: &str,
+) {
+    match request_context.clone().connection_id {
+        Some(connection_id) => {
+            let message = serde_json::to_string(&json!({ "role": role, "status": status }))
+                .unwrap_or_default();
+            send(request_context, connection_id, message).await;
+        }
+        None => {}
+    }
+}
+
+pub async fn rate_limited(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    retry_after: u64,
+) {
+    match request_context.clone().connection_id {
+        Some(connection_id) => {
+            let message = serde_json::to_string(
+                &json!({ "status": "rate_limited", "retry_after": retry_after }),
+            )
+            .unwrap_or_default();
+            send(request_context, connection_id, message).await;
+        }
+        None => {}
+    }
+}
+
+pub async fn join_code(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    code: String,
+    url: String,
+    qr: join::Qr,
+) {
+    match request_context.clone().connection_id {
+        Some(connection_id) => {
+            let message = serde_json::to_string(&json!({
+                "status": "join_code",
+                "code": code,
+                "url": url,
+                "qr": { "mime": qr.mime, "data": base64::encode(&qr.data) }
+            }))
+            .unwrap_or_default();
+            send(request_context, connection_id, message).await;
+        }
+        None => {}
+    }
+}
+
+fn player_info(player: &models::Connection) -> serde_json::Value {
+    json!({
+        "connection": player.id,
+        "nickname": player.nickname,
+        "player": player.player_id,
+        "role": player.role,
+        "que": player.que,
+        "slot": player.slot,
+        "table": player.table_id,
+        "source_ip": player.source_ip,
+        "user_agent": player.user_agent,
+        "device": player.device,
+        "connected_at": player.connected_at,
+    })
+}
+
 pub async fn inform_server(
     request_context: ApiGatewayWebsocketProxyRequestContext,
-    id: String,
+    player: &models::Connection,
     admin_id: String,
     status: String,
 ) {
+    let mut info = player_info(player);
+    info["status"] = json!(status);
+    let message = serde_json::to_string(&info).unwrap_or_default();
+    send(request_context, admin_id, message).await;
+}
+
+pub async fn snapshot(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin_id: String,
+    connections: &[models::Connection],
+) {
+    let connections: Vec<_> = connections.iter().map(player_info).collect();
     let message =
-        serde_json::to_string(&json!({ "connection": id, "status": status})).unwrap_or_default();
+        serde_json::to_string(&json!({ "status": "snapshot", "connections": connections }))
+            .unwrap_or_default();
     send(request_context, admin_id, message).await;
 }
 
-pub async fn send(
+pub async fn forward(
     request_context: ApiGatewayWebsocketProxyRequestContext,
-    connection_id: String,
+    player: &models::Connection,
+    admin_id: String,
     message: String,
 ) {
+    let data = serde_json::from_str::<serde_json::Value>(&message)
+        .unwrap_or(serde_json::Value::String(message));
+    let envelope = serde_json::to_string(&json!({
+        "connection": player.id,
+        "slot": player.slot,
+        "nickname": player.nickname,
+        "data": data
+    }))
+    .unwrap_or_default();
+    send(request_context, admin_id, envelope).await;
+}
+
+pub async fn que(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin_id: String,
+    queued: &[models::Connection],
+) {
+    let queued: Vec<_> = queued.iter().map(player_info).collect();
+    let message =
+        serde_json::to_string(&json!({ "status": "que", "que": queued })).unwrap_or_default();
+    send(request_context, admin_id, message).await;
+}
+
+pub async fn cooldown(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    role: models::Role,
+    remaining: u64,
+) {
+    match request_context.clone().connection_id {
+        Some(connection_id) => {
+            let message = serde_json::to_string(
+                &json!({ "role": role, "status": "cooldown", "remaining": remaining }),
+            )
+            .unwrap_or_default();
+            send(request_context, connection_id, message).await;
+        }
+        None => {}
+    }
+}
+
+pub async fn vote_rejected(request_context: ApiGatewayWebsocketProxyRequestContext, reason: &str) {
+    match request_context.clone().connection_id {
+        Some(connection_id) => {
+            let message =
+                serde_json::to_string(&json!({ "status": "vote_rejected", "reason": reason }))
+                    .unwrap_or_default();
+            send(request_context, connection_id, message).await;
+        }
+        None => {}
+    }
+}
+
+pub async fn permission_denied(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    route: &str,
+    message_type: Option<&str>,
+    recipient: Option<&str>,
+) {
+    match request_context.clone().connection_id {
+        Some(connection_id) => {
+            let message = serde_json::to_string(&json!({
+                "status": "permission_denied",
+                "route": route,
+                "type": message_type,
+                "recipient": recipient
+            }))
+            .unwrap_or_default();
+            send(request_context, connection_id, message).await;
+        }
+        None => {}
+    }
+}
+
+pub async fn message_rejected(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    reason: &str,
+) {
+    match request_context.clone().connection_id {
+        Some(connection_id) => {
+            let message =
+                serde_json::to_string(&json!({ "status": "message_rejected", "reason": reason }))
+                    .unwrap_or_default();
+            send(request_context, connection_id, message).await;
+        }
+        None => {}
+    }
+}
+
+pub async fn crowd(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection_id: String,
+    options: Option<&[String]>,
+) {
+    let status = match options {
+        Some(_) => "crowd_open",
+        None => "crowd_closed",
+    };
+    let message =
+        serde_json::to_string(&json!({ "status": status, "options": options })).unwrap_or_default();
+    send(request_context, connection_id, message).await;
+}
+
+pub async fn crowd_tally(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin_id: String,
+    window: u64,
+    votes: &HashMap<String, i64>,
+) {
+    let message = serde_json::to_string(&json!({
+        "status": "crowd_tally",
+        "window": window,
+        "votes": votes,
+        "total": votes.values().sum::<i64>()
+    }))
+    .unwrap_or_default();
+    send(request_context, admin_id, message).await;
+}
+
+pub async fn poll(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection_id: String,
+    poll: &models::Poll,
+) {
+    let message = serde_json::to_string(&json!({
+        "status": if poll.closed { "poll_closed" } else { "poll" },
+        "poll": poll.id,
+        "question": poll.question,
+        "options": poll.options,
+        "deadline": poll.deadline,
+        "results": poll.results
+    }))
+    .unwrap_or_default();
+    send(request_context, connection_id, message).await;
+}
+
+pub async fn poll_tally(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin_id: String,
+    poll: &models::Poll,
+    votes: &HashMap<String, i64>,
+) {
+    let message = serde_json::to_string(&json!({
+        "status": "poll_tally",
+        "poll": poll.id,
+        "votes": votes,
+        "total": votes.values().sum::<i64>()
+    }))
+    .unwrap_or_default();
+    send(request_context, admin_id, message).await;
+}
+
+pub async fn polls(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection_id: String,
+    polls: &[models::Poll],
+) {
+    let message =
+        serde_json::to_string(&json!({ "status": "polls", "polls": polls })).unwrap_or_default();
+    send(request_context, connection_id, message).await;
+}
+
+pub async fn reactions(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin_id: String,
+    window: u64,
+    counts: &HashMap<String, i64>,
+) {
+    let message = serde_json::to_string(&json!({
+        "status": "reactions",
+        "window": window,
+        "counts": counts
+    }))
+    .unwrap_or_default();
+    send(request_context, admin_id, message).await;
+}
+
+pub async fn submission_rejected(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    reason: &str,
+) {
+    match request_context.clone().connection_id {
+        Some(connection_id) => {
+            let message = serde_json::to_string(
+                &json!({ "status": "submission_rejected", "reason": reason }),
+            )
+            .unwrap_or_default();
+            send(request_context, connection_id, message).await;
+        }
+        None => {}
+    }
+}
+
+pub async fn submission_status(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection_id: String,
+    submission: &models::Submission,
+) {
+    let message = serde_json::to_string(&json!({
+        "status": format!("submission_{}", submission.status),
+        "submission": submission.id,
+        "reason": submission.reason
+    }))
+    .unwrap_or_default();
+    send(request_context, connection_id, message).await;
+}
+
+pub async fn submission(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin_id: String,
+    submission: &models::Submission,
+) {
+    let message =
+        serde_json::to_string(&json!({ "status": "submission", "submission": submission }))
+            .unwrap_or_default();
+    send(request_context, admin_id, message).await;
+}
+
+pub async fn submissions(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin_id: String,
+    submissions: &[models::Submission],
+) {
+    let message =
+        serde_json::to_string(&json!({ "status": "submissions", "submissions": submissions }))
+            .unwrap_or_default();
+    send(request_context, admin_id, message).await;
+}
+
+pub async fn display_submission(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin_id: String,
+    submission: &models::Submission,
+) {
+    let message = serde_json::to_string(&json!({
+        "status": "display_submission",
+        "submission": submission.id,
+        "nickname": submission.nickname,
+        "type": submission.content_type,
+        "body": submission.body
+    }))
+    .unwrap_or_default();
+    send(request_context, admin_id, message).await;
+}
+
+pub async fn canvas(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection_id: String,
+    canvas: &Canvas,
+    data: String,
+) {
+    let message = serde_json::to_string(&json!({
+        "status": "canvas",
+        "width": canvas.width,
+        "height": canvas.height,
+        "palette": canvas.palette,
+        "encoding": "deflate+base64",
+        "data": data
+    }))
+    .unwrap_or_default();
+    send(request_context, connection_id, message).await;
+}
+
+pub async fn image(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin_id: String,
+    target: &str,
+    data: &[u8],
+) {
+    let message = serde_json::to_string(&json!({
+        "status": "image",
+        "target": target,
+        "mime": "image/png",
+        "data": base64::encode(data)
+    }))
+    .unwrap_or_default();
+    send(request_context, admin_id, message).await;
+}
+
+pub async fn canvas_diff(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin_id: String,
+    pixels: &[[u32; 3]],
+) {
+    let message = serde_json::to_string(&json!({ "status": "canvas_diff", "pixels": pixels }))
+        .unwrap_or_default();
+    send(request_context, admin_id, message).await;
+}
+
+pub async fn canvas_rejected(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    reason: &str,
+) {
+    match request_context.clone().connection_id {
+        Some(connection_id) => {
+            let message =
+                serde_json::to_string(&json!({ "status": "canvas_rejected", "reason": reason }))
+                    .unwrap_or_default();
+            send(request_context, connection_id, message).await;
+        }
+        None => {}
+    }
+}
+
+pub async fn canvas_cooldown(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    remaining: u64,
+) {
+    match request_context.clone().connection_id {
+        Some(connection_id) => {
+            let message = serde_json::to_string(
+                &json!({ "status": "cooldown", "mode": "canvas", "remaining": remaining }),
+            )
+            .unwrap_or_default();
+            send(request_context, connection_id, message).await;
+        }
+        None => {}
+    }
+}
+
+pub async fn quiz_loaded(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin_id: String,
+    quiz: &models::Quiz,
+    questions: usize,
+) {
+    let message = serde_json::to_string(&json!({
+        "status": "quiz_loaded",
+        "title": quiz.title,
+        "questions": questions
+    }))
+    .unwrap_or_default();
+    send(request_context, admin_id, message).await;
+}
+
+pub async fn quiz_question(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection_id: String,
+    quiz: &models::Quiz,
+    question: &Question,
+) {
+    let message = serde_json::to_string(&json!({
+        "status": "quiz_question",
+        "question": quiz.current,
+        "text": question.text,
+        "options": question.options,
+        "deadline": quiz.deadline
+    }))
+    .unwrap_or_default();
+    send(request_context, connection_id, message).await;
+}
+
+pub async fn quiz_round(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection_id: String,
+    quiz: &models::Quiz,
+    question: &Question,
+    standings: &[Standing],
+) {
+    let message = serde_json::to_string(&json!({
+        "status": "quiz_round",
+        "question": quiz.current,
+        "answer": question.answer,
+        "standings": standings
+    }))
+    .unwrap_or_default();
+    send(request_context, connection_id, message).await;
+}
+
+pub async fn quiz_finished(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection_id: String,
+    quiz: &models::Quiz,
+    standings: &[Standing],
+) {
+    let message = serde_json::to_string(&json!({
+        "status": "quiz_finished",
+        "title": quiz.title,
+        "standings": standings
+    }))
+    .unwrap_or_default();
+    send(request_context, connection_id, message).await;
+}
+
+pub async fn quiz_answered(request_context: ApiGatewayWebsocketProxyRequestContext, question: i64) {
+    match request_context.clone().connection_id {
+        Some(connection_id) => {
+            let message =
+                serde_json::to_string(&json!({ "status": "quiz_answered", "question": question }))
+                    .unwrap_or_default();
+            send(request_context, connection_id, message).await;
+        }
+        None => {}
+    }
+}
+
+pub async fn quiz_rejected(request_context: ApiGatewayWebsocketProxyRequestContext, reason: &str) {
+    match request_context.clone().connection_id {
+        Some(connection_id) => {
+            let message =
+                serde_json::to_string(&json!({ "status": "quiz_rejected", "reason": reason }))
+                    .unwrap_or_default();
+            send(request_context, connection_id, message).await;
+        }
+        None => {}
+    }
+}
+
+pub async fn identity(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    player_id: String,
+    token: String,
+) {
+    match request_context.clone().connection_id {
+        Some(connection_id) => {
+            let message = serde_json::to_string(&json!({
+                "status": "identity",
+                "player": player_id,
+                "token": token
+            }))
+            .unwrap_or_default();
+            send(request_context, connection_id, message).await;
+        }
+        None => {}
+    }
+}
+
+pub async fn invalid_nickname(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    reason: &str,
+) {
+    match request_context.clone().connection_id {
+        Some(connection_id) => {
+            let message =
+                serde_json::to_string(&json!({ "status": "invalid_nickname", "reason": reason }))
+                    .unwrap_or_default();
+            send(request_context, connection_id, message).await;
+        }
+        None => {}
+    }
+}
+
+pub async fn leaderboard(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection_id: String,
+    leaderboard: &[models::PlayerStats],
+) {
+    let message =
+        serde_json::to_string(&json!({ "status": "leaderboard", "leaderboard": leaderboard }))
+            .unwrap_or_default();
+    send(request_context, connection_id, message).await;
+}
+
+pub async fn bracket(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection_id: String,
+    bracket: &Bracket,
+) {
+    let message = serde_json::to_string(&json!({ "status": "bracket", "bracket": bracket }))
+        .unwrap_or_default();
+    send(request_context, connection_id, message).await;
+}
+
+pub async fn bans(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin_id: String,
+    bans: &[models::Ban],
+) {
+    let message =
+        serde_json::to_string(&json!({ "status": "bans", "bans": bans })).unwrap_or_default();
+    send(request_context, admin_id, message).await;
+}
+
+fn client(
+    request_context: &ApiGatewayWebsocketProxyRequestContext,
+) -> ApiGatewayManagementApiClient {
     let default_region = Region::default().name().to_owned();
-    let client = ApiGatewayManagementApiClient::new(Region::Custom {
+    ApiGatewayManagementApiClient::new(Region::Custom {
         name: default_region,
-        endpoint: endpoint(&request_context),
-    });
-    let reply_result = client
+        endpoint: endpoint(request_context),
+    })
+}
+
+pub async fn disconnect(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection_id: String,
+) {
+    let res = client(&request_context)
+        .delete_connection(DeleteConnectionRequest { connection_id })
+        .await;
+
+    if let Err(err) = res {
+        debug!("error closing connection {:?}", err);
+    }
+}
+
+pub async fn send(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection_id: String,
+    message: String,
+) {
+    let reply_result = client(&request_context)
         .post_to_connection(PostToConnectionRequest {
             connection_id: connection_id.clone(),
             data: Bytes::from(message),
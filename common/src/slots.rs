@@ -0,0 +1,91 @@
+use crate::connection_operations::*;
+use crate::error::Error;
+use crate::models::*;
+use crate::store;
+use crate::time;
+use dynomite::attr_map;
+
+const KIND: &str = "slot";
+const STALE_AFTER: u64 = 30_000;
+
+fn claim_id(seat: &Seat) -> String {
+    format!("{}#{}#{}", KIND, seat.table_id, seat.slot)
+}
+
+pub async fn claim(seat: &Seat, holder: &str) -> Result<bool, Error> {
+    let claim = SlotClaim {
+        id: claim_id(seat),
+        kind: KIND.to_string(),
+        table_id: seat.table_id.clone(),
+        slot: seat.slot,
+        holder: holder.to_string(),
+        claimed_at: time::now_millis(),
+    };
+    if store::create_record(claim.clone()).await? {
+        return Ok(true);
+    }
+
+    let existing: SlotClaim = match store::get_record(claim.id.clone()).await? {
+        Some(existing) => existing,
+        None => return store::create_record(claim).await,
+    };
+    if existing.holder == holder {
+        return Ok(true);
+    }
+    if !is_stale(&existing).await {
+        return Ok(false);
+    }
+    store::put_record_if(claim, attr_map!("holder" => existing.holder)).await
+}
+
+pub async fn take(seat: &Seat, holder: &str) -> Result<(), Error> {
+    store::put_record(SlotClaim {
+        id: claim_id(seat),
+        kind: KIND.to_string(),
+        table_id: seat.table_id.clone(),
+        slot: seat.slot,
+        holder: holder.to_string(),
+        claimed_at: time::now_millis(),
+    })
+    .await
+}
+
+pub async fn release(seat: &Seat, holder: &str) -> Result<(), Error> {
+    store::delete_record_if(claim_id(seat), attr_map!("holder" => holder.to_string())).await?;
+    Ok(())
+}
+
+pub async fn vacate(player: &Connection) -> Result<(), Error> {
+    match player.seat() {
+        Some(seat) => release(&seat, &player.id).await,
+        None => Ok(()),
+    }
+}
+
+pub async fn claim_free(role: Role, holder: &str) -> Result<Option<Seat>, Error> {
+    for seat in find_free_seats(role).await? {
+        if claim(&seat, holder).await? {
+            return Ok(Some(seat));
+        }
+    }
+    Ok(None)
+}
+
+// A holder that never took its seat, or has since left it, no longer blocks the slot.
+async fn is_stale(claim: &SlotClaim) -> bool {
+    if time::now_millis() < claim.claimed_at + STALE_AFTER {
+        return false;
+    }
+    let seat = Seat {
+        table_id: claim.table_id.clone(),
+        slot: claim.slot,
+    };
+    match find_connection_in_db(UnresolvedConnection {
+        id: claim.holder.clone(),
+    })
+    .await
+    {
+        Ok(holder) => holder.seat() != Some(seat),
+        Err(_) => true,
+    }
+}
@@ -0,0 +1,74 @@
+use crate::canvas;
+use crate::config::{self, SnapshotStorage};
+use crate::error::Error;
+use crate::leaderboard;
+use crate::render;
+use crate::time;
+use crate::tournament;
+use rusoto_core::Region;
+use rusoto_s3::{PutObjectRequest, S3Client, S3};
+use std::path::Path;
+
+pub async fn render_target(target: &str, table_id: &str) -> Result<Option<Vec<u8>>, Error> {
+    match target {
+        "canvas" => Ok(Some(render::canvas(
+            &canvas::load().await?,
+            config::snapshot_scale(),
+        )?)),
+        "leaderboard" => Ok(Some(render::leaderboard(
+            &leaderboard::top(config::leaderboard_size()).await?,
+        )?)),
+        "bracket" => match tournament::find(table_id).await? {
+            Some(bracket) => Ok(Some(render::bracket(&bracket)?)),
+            None => Ok(None),
+        },
+        _ => Err("Unknown render target".into()),
+    }
+}
+
+pub async fn capture_due() -> Result<(), Error> {
+    let interval = config::snapshot_interval();
+    let now = time::now_seconds();
+    if interval == 0 || (now / 60) % interval != 0 {
+        return Ok(());
+    }
+    capture(now).await
+}
+
+pub async fn capture(timestamp: u64) -> Result<(), Error> {
+    for target in &["canvas", "leaderboard"] {
+        if let Some(image) = render_target(target, "").await? {
+            write(&format!("{}/{}.png", timestamp, target), image).await?;
+        }
+    }
+    for (table_id, bracket) in tournament::all().await? {
+        let image = render::bracket(&bracket)?;
+        write(&format!("{}/bracket-{}.png", timestamp, table_id), image).await?;
+    }
+    Ok(())
+}
+
+async fn write(name: &str, image: Vec<u8>) -> Result<(), Error> {
+    match config::snapshot_storage() {
+        SnapshotStorage::Local(directory) => {
+            let path = Path::new(&directory).join(name);
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(path, image).await?;
+        }
+        SnapshotStorage::S3 { bucket, prefix } => {
+            let client = S3Client::new(Region::default());
+            client
+                .put_object(PutObjectRequest {
+                    bucket,
+                    key: format!("{}{}", prefix, name),
+                    body: Some(image.into()),
+                    content_type: Some("image/png".to_string()),
+                    ..Default::default()
+                })
+                .await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,407 @@
+use super::error::Error;
+use dynomite::{
+    attr_map,
+    dynamodb::{
+        BatchGetItemInput, DeleteItemError, DeleteItemInput, DynamoDb, DynamoDbClient,
+        GetItemInput, KeysAndAttributes, PutItemError, PutItemInput, ScanInput, UpdateItemError,
+        UpdateItemInput,
+    },
+    Attributes, FromAttributes,
+};
+use rusoto_core::RusotoError;
+use std::collections::{HashMap, HashSet};
+use std::env;
+
+const BATCH_GET_LIMIT: usize = 100;
+
+pub fn get_store_table() -> String {
+    env::var("storeTable").unwrap_or_default()
+}
+
+pub async fn get_record<T: FromAttributes>(id: String) -> Result<Option<T>, Error> {
+    let client = DynamoDbClient::new(Default::default());
+
+    let res = client
+        .get_item(GetItemInput {
+            table_name: get_store_table(),
+            key: attr_map!("id" => id),
+            ..GetItemInput::default()
+        })
+        .await?;
+
+    match res.item {
+        Some(item) => Ok(Some(T::from_attrs(item)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn get_records<T: FromAttributes>(ids: Vec<String>) -> Result<Vec<T>, Error> {
+    let client = DynamoDbClient::new(Default::default());
+    let table_name = get_store_table();
+    let mut ids = ids;
+    ids.sort();
+    ids.dedup();
+
+    let mut records = Vec::new();
+    for chunk in ids.chunks(BATCH_GET_LIMIT) {
+        let mut keys: Vec<Attributes> = chunk
+            .iter()
+            .map(|id| attr_map!("id" => id.clone()))
+            .collect();
+        while !keys.is_empty() {
+            let mut request_items = HashMap::new();
+            request_items.insert(
+                table_name.clone(),
+                KeysAndAttributes {
+                    keys,
+                    ..KeysAndAttributes::default()
+                },
+            );
+            let res = client
+                .batch_get_item(BatchGetItemInput {
+                    request_items,
+                    ..BatchGetItemInput::default()
+                })
+                .await?;
+
+            for item in res
+                .responses
+                .and_then(|mut responses| responses.remove(&table_name))
+                .unwrap_or_default()
+            {
+                records.push(T::from_attrs(item)?);
+            }
+            keys = res
+                .unprocessed_keys
+                .and_then(|mut unprocessed| unprocessed.remove(&table_name))
+                .map(|unprocessed| unprocessed.keys)
+                .unwrap_or_default();
+        }
+    }
+    Ok(records)
+}
+
+pub async fn put_record<T: Into<Attributes>>(record: T) -> Result<(), Error> {
+    let client = DynamoDbClient::new(Default::default());
+
+    client
+        .put_item(PutItemInput {
+            table_name: get_store_table(),
+            item: record.into(),
+            ..PutItemInput::default()
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn create_record<T: Into<Attributes>>(record: T) -> Result<bool, Error> {
+    let client = DynamoDbClient::new(Default::default());
+
+    let res = client
+        .put_item(PutItemInput {
+            table_name: get_store_table(),
+            item: record.into(),
+            condition_expression: Some("attribute_not_exists(id)".to_string()),
+            ..PutItemInput::default()
+        })
+        .await;
+
+    match res {
+        Ok(_) => Ok(true),
+        Err(RusotoError::Service(PutItemError::ConditionalCheckFailed(_))) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub async fn put_record_if<T: Into<Attributes>>(
+    record: T,
+    expected: Attributes,
+) -> Result<bool, Error> {
+    let client = DynamoDbClient::new(Default::default());
+    let (condition_expression, expression_attribute_names, expression_attribute_values) =
+        conditions(expected);
+
+    let res = client
+        .put_item(PutItemInput {
+            table_name: get_store_table(),
+            item: record.into(),
+            condition_expression: Some(condition_expression),
+            expression_attribute_names: Some(expression_attribute_names),
+            expression_attribute_values: Some(expression_attribute_values),
+            ..PutItemInput::default()
+        })
+        .await;
+
+    match res {
+        Ok(_) => Ok(true),
+        Err(RusotoError::Service(PutItemError::ConditionalCheckFailed(_))) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub async fn update_record(id: String, fields: Attributes) -> Result<(), Error> {
+    let client = DynamoDbClient::new(Default::default());
+
+    let mut expression_attribute_names = HashMap::new();
+    let mut expression_attribute_values = HashMap::new();
+    let mut assignments = Vec::new();
+    for (index, (name, value)) in fields.into_iter().enumerate() {
+        expression_attribute_names.insert(format!("#F{}", index), name);
+        expression_attribute_values.insert(format!(":f{}", index), value);
+        assignments.push(format!("#F{} = :f{}", index, index));
+    }
+
+    client
+        .update_item(UpdateItemInput {
+            table_name: get_store_table(),
+            key: attr_map!("id" => id),
+            update_expression: Some(format!("SET {}", assignments.join(", "))),
+            expression_attribute_names: Some(expression_attribute_names),
+            expression_attribute_values: Some(expression_attribute_values),
+            ..UpdateItemInput::default()
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn update_record_if(
+    id: String,
+    fields: Attributes,
+    expected: Attributes,
+) -> Result<bool, Error> {
+    let client = DynamoDbClient::new(Default::default());
+    let (condition_expression, mut expression_attribute_names, mut expression_attribute_values) =
+        conditions(expected);
+
+    let mut assignments = Vec::new();
+    for (index, (name, value)) in fields.into_iter().enumerate() {
+        expression_attribute_names.insert(format!("#F{}", index), name);
+        expression_attribute_values.insert(format!(":f{}", index), value);
+        assignments.push(format!("#F{} = :f{}", index, index));
+    }
+
+    let res = client
+        .update_item(UpdateItemInput {
+            table_name: get_store_table(),
+            key: attr_map!("id" => id),
+            update_expression: Some(format!("SET {}", assignments.join(", "))),
+            condition_expression: Some(condition_expression),
+            expression_attribute_names: Some(expression_attribute_names),
+            expression_attribute_values: Some(expression_attribute_values),
+            ..UpdateItemInput::default()
+        })
+        .await;
+
+    match res {
+        Ok(_) => Ok(true),
+        Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub async fn increment_record(id: String, counter: &str, fields: Attributes) -> Result<(), Error> {
+    let client = DynamoDbClient::new(Default::default());
+
+    let mut expression_attribute_names = HashMap::new();
+    let mut expression_attribute_values = attr_map!(":one" => 1_i64);
+    let mut assignments = Vec::new();
+    for (index, (name, value)) in fields.into_iter().enumerate() {
+        expression_attribute_names.insert(format!("#F{}", index), name);
+        expression_attribute_values.insert(format!(":f{}", index), value);
+        assignments.push(format!("#F{} = :f{}", index, index));
+    }
+    expression_attribute_names.insert("#C".to_string(), counter.to_string());
+
+    let update_expression = if assignments.is_empty() {
+        "ADD #C :one".to_string()
+    } else {
+        format!("SET {} ADD #C :one", assignments.join(", "))
+    };
+
+    client
+        .update_item(UpdateItemInput {
+            table_name: get_store_table(),
+            key: attr_map!("id" => id),
+            update_expression: Some(update_expression),
+            expression_attribute_names: Some(expression_attribute_names),
+            expression_attribute_values: Some(expression_attribute_values),
+            ..UpdateItemInput::default()
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn add_to_set(
+    id: String,
+    set: &str,
+    value: String,
+    fields: Attributes,
+) -> Result<(), Error> {
+    let client = DynamoDbClient::new(Default::default());
+
+    let value: HashSet<String> = vec![value].into_iter().collect();
+    let mut expression_attribute_names = HashMap::new();
+    let mut expression_attribute_values = attr_map!(":value" => value);
+    let mut assignments = Vec::new();
+    for (index, (name, value)) in fields.into_iter().enumerate() {
+        expression_attribute_names.insert(format!("#F{}", index), name);
+        expression_attribute_values.insert(format!(":f{}", index), value);
+        assignments.push(format!("#F{} = :f{}", index, index));
+    }
+    expression_attribute_names.insert("#S".to_string(), set.to_string());
+
+    let update_expression = if assignments.is_empty() {
+        "ADD #S :value".to_string()
+    } else {
+        format!("SET {} ADD #S :value", assignments.join(", "))
+    };
+
+    client
+        .update_item(UpdateItemInput {
+            table_name: get_store_table(),
+            key: attr_map!("id" => id),
+            update_expression: Some(update_expression),
+            expression_attribute_names: Some(expression_attribute_names),
+            expression_attribute_values: Some(expression_attribute_values),
+            ..UpdateItemInput::default()
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn remove_from_set(id: String, set: &str, value: String) -> Result<(), Error> {
+    let client = DynamoDbClient::new(Default::default());
+
+    let value: HashSet<String> = vec![value].into_iter().collect();
+    let mut expression_attribute_names = HashMap::new();
+    expression_attribute_names.insert("#S".to_string(), set.to_string());
+
+    client
+        .update_item(UpdateItemInput {
+            table_name: get_store_table(),
+            key: attr_map!("id" => id),
+            update_expression: Some("DELETE #S :value".to_string()),
+            expression_attribute_names: Some(expression_attribute_names),
+            expression_attribute_values: Some(attr_map!(":value" => value)),
+            ..UpdateItemInput::default()
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn take_from_set(id: String, set: &str, value: String) -> Result<bool, Error> {
+    let client = DynamoDbClient::new(Default::default());
+
+    let values: HashSet<String> = vec![value.clone()].into_iter().collect();
+    let mut expression_attribute_names = HashMap::new();
+    expression_attribute_names.insert("#S".to_string(), set.to_string());
+
+    let res = client
+        .update_item(UpdateItemInput {
+            table_name: get_store_table(),
+            key: attr_map!("id" => id),
+            update_expression: Some("DELETE #S :values".to_string()),
+            condition_expression: Some("contains(#S, :value)".to_string()),
+            expression_attribute_names: Some(expression_attribute_names),
+            expression_attribute_values: Some(attr_map!(":values" => values, ":value" => value)),
+            ..UpdateItemInput::default()
+        })
+        .await;
+
+    match res {
+        Ok(_) => Ok(true),
+        Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub async fn delete_record(id: String) -> Result<(), Error> {
+    let client = DynamoDbClient::new(Default::default());
+
+    client
+        .delete_item(DeleteItemInput {
+            table_name: get_store_table(),
+            key: attr_map!("id" => id),
+            ..DeleteItemInput::default()
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn delete_record_if(id: String, expected: Attributes) -> Result<bool, Error> {
+    let client = DynamoDbClient::new(Default::default());
+    let (condition_expression, expression_attribute_names, expression_attribute_values) =
+        conditions(expected);
+
+    let res = client
+        .delete_item(DeleteItemInput {
+            table_name: get_store_table(),
+            key: attr_map!("id" => id),
+            condition_expression: Some(condition_expression),
+            expression_attribute_names: Some(expression_attribute_names),
+            expression_attribute_values: Some(expression_attribute_values),
+            ..DeleteItemInput::default()
+        })
+        .await;
+
+    match res {
+        Ok(_) => Ok(true),
+        Err(RusotoError::Service(DeleteItemError::ConditionalCheckFailed(_))) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn conditions(expected: Attributes) -> (String, HashMap<String, String>, Attributes) {
+    let mut expression_attribute_names = HashMap::new();
+    let mut expression_attribute_values = HashMap::new();
+    let mut conditions = Vec::new();
+    for (index, (name, value)) in expected.into_iter().enumerate() {
+        expression_attribute_names.insert(format!("#C{}", index), name);
+        expression_attribute_values.insert(format!(":c{}", index), value);
+        conditions.push(format!("#C{} = :c{}", index, index));
+    }
+    (
+        conditions.join(" and "),
+        expression_attribute_names,
+        expression_attribute_values,
+    )
+}
+
+pub async fn scan_records<T: FromAttributes>(kind: &str) -> Result<Vec<T>, Error> {
+    let client = DynamoDbClient::new(Default::default());
+    let mut expression_attribute_names = HashMap::new();
+    expression_attribute_names.insert("#K".to_string(), "kind".to_string());
+
+    let mut records = Vec::new();
+    let mut exclusive_start_key = None;
+
+    loop {
+        let res = client
+            .scan(ScanInput {
+                table_name: get_store_table(),
+                expression_attribute_names: Some(expression_attribute_names.clone()),
+                expression_attribute_values: Some(attr_map!(
+                    ":kind" => kind.to_string()
+                )),
+                filter_expression: Some("#K = :kind".into()),
+                exclusive_start_key,
+                ..ScanInput::default()
+            })
+            .await?;
+
+        for item in res.items.unwrap_or_default() {
+            records.push(T::from_attrs(item)?);
+        }
+
+        exclusive_start_key = res.last_evaluated_key;
+        if exclusive_start_key.is_none() {
+            return Ok(records);
+        }
+    }
+}
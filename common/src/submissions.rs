@@ -0,0 +1,355 @@
+use crate::config;
+use crate::connection_operations::*;
+use crate::error::Error;
+use crate::filter;
+use crate::models::*;
+use crate::send;
+use crate::store;
+use crate::time;
+use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequestContext;
+use dynomite::attr_map;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+const KIND: &str = "submission";
+const RETENTION: u64 = 7 * 24 * 60 * 60;
+const MAX_COLOR_LENGTH: usize = 16;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SubmissionContent {
+    Text { text: String },
+    Strokes { strokes: Vec<Stroke> },
+    Png { data: String },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Stroke {
+    pub color: Option<String>,
+    pub width: Option<f32>,
+    pub points: Vec<[f32; 2]>,
+}
+
+pub fn validate(
+    content: SubmissionContent,
+    blocked: &[String],
+) -> Result<(&'static str, String), &'static str> {
+    match content {
+        SubmissionContent::Text { text } => {
+            let text = text.trim().to_string();
+            if text.is_empty() {
+                return Err("empty");
+            }
+            if text.chars().count() > config::submission_text_limit() {
+                return Err("too_large");
+            }
+            if filter::is_blocked(&text, blocked) {
+                return Err("blocked");
+            }
+            Ok(("text", text))
+        }
+        SubmissionContent::Strokes { strokes } => {
+            let points: usize = strokes.iter().map(|stroke| stroke.points.len()).sum();
+            if points == 0 {
+                return Err("empty");
+            }
+            if points > config::submission_stroke_points() {
+                return Err("too_large");
+            }
+            let valid = strokes.iter().all(|stroke| {
+                stroke
+                    .color
+                    .as_ref()
+                    .map_or(true, |color| color.len() <= MAX_COLOR_LENGTH)
+                    && stroke
+                        .width
+                        .map_or(true, |width| width.is_finite() && width > 0.0)
+                    && stroke
+                        .points
+                        .iter()
+                        .all(|point| point.iter().all(|value| value.is_finite()))
+            });
+            if !valid {
+                return Err("invalid");
+            }
+            Ok((
+                "strokes",
+                serde_json::to_string(&strokes).map_err(|_| "invalid")?,
+            ))
+        }
+        SubmissionContent::Png { data } => {
+            let bytes = base64::decode(&data).map_err(|_| "invalid")?;
+            if bytes.len() > config::submission_image_bytes() {
+                return Err("too_large");
+            }
+            let (info, _) = png::Decoder::new(&bytes[..])
+                .read_info()
+                .map_err(|_| "invalid")?;
+            let size = config::submission_image_size();
+            if info.width > size || info.height > size {
+                return Err("too_large");
+            }
+            Ok(("png", data))
+        }
+    }
+}
+
+pub async fn submit(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    connection: &Connection,
+    content: SubmissionContent,
+) -> Result<(), Error> {
+    let (content_type, body) = match validate(content, &filter::words()) {
+        Ok(validated) => validated,
+        Err(reason) => {
+            send::submission_rejected(request_context, reason).await;
+            return Ok(());
+        }
+    };
+    let display = find_display_admin(connection).await?;
+
+    let submitted_at = time::now_millis();
+    let submission = Submission {
+        id: format!(
+            "{}#{}#{}",
+            KIND,
+            submitted_at,
+            hex::encode(rand::thread_rng().gen::<[u8; 4]>())
+        ),
+        kind: KIND.to_string(),
+        table_id: display.id.clone(),
+        connection_id: connection.id.clone(),
+        player_id: connection.player_id.clone(),
+        nickname: connection.nickname.clone(),
+        content_type: content_type.to_string(),
+        body,
+        status: "pending".to_string(),
+        reason: None,
+        delivered: false,
+        submitted_at,
+        expires_at: Some(time::now_seconds() + RETENTION),
+    };
+    store::put_record(submission.clone()).await?;
+    store::add_to_set(
+        queue_id(&display.id),
+        "ids",
+        submission.id.clone(),
+        attr_map!(
+            "kind" => format!("{}_queue", KIND),
+            "table_id" => display.id.clone(),
+            "clearAt" => time::now_seconds() + RETENTION
+        ),
+    )
+    .await?;
+
+    send::submission_status(request_context.clone(), connection.id.clone(), &submission).await;
+    send::submission(request_context, display.id, &submission).await;
+    Ok(())
+}
+
+pub async fn pending(admin: &Connection) -> Result<Vec<Submission>, Error> {
+    Ok(queue(&admin.id)
+        .await?
+        .into_iter()
+        .filter(|submission| submission.status == "pending")
+        .collect())
+}
+
+pub async fn moderate(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+    id: String,
+    approve: bool,
+    reason: Option<String>,
+) -> Result<(), Error> {
+    let submission: Option<Submission> = store::get_record(id).await?;
+    let mut submission = submission.ok_or("Unknown submission")?;
+    if submission.table_id != admin.id {
+        return Err("Submission belongs to another display".into());
+    }
+
+    submission.status = if approve { "approved" } else { "rejected" }.to_string();
+    submission.reason = reason;
+    let mut fields = attr_map!("status" => submission.status.clone());
+    if let Some(reason) = &submission.reason {
+        fields.extend(attr_map!("reason" => reason.clone()));
+    }
+    if !store::update_record_if(
+        submission.id.clone(),
+        fields,
+        attr_map!("status" => "pending".to_string()),
+    )
+    .await?
+    {
+        return Ok(());
+    }
+    send::submission_status(
+        request_context.clone(),
+        submission.connection_id.clone(),
+        &submission,
+    )
+    .await;
+
+    deliver(request_context, admin).await
+}
+
+async fn deliver(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+) -> Result<(), Error> {
+    // A submission left pending longer than the hold stops blocking the ones behind it.
+    let hold_until = time::now_millis().saturating_sub(config::submission_hold() * 1000);
+    for mut submission in queue(&admin.id).await? {
+        match submission.status.as_ref() {
+            "pending" if submission.submitted_at > hold_until => break,
+            "pending" => {}
+            "approved" if !submission.delivered => {
+                submission.delivered = true;
+                if store::update_record_if(
+                    submission.id.clone(),
+                    attr_map!("delivered" => true),
+                    attr_map!("delivered" => false),
+                )
+                .await?
+                {
+                    store::remove_from_set(queue_id(&admin.id), "ids", submission.id.clone())
+                        .await?;
+                    send::display_submission(
+                        request_context.clone(),
+                        admin.id.clone(),
+                        &submission,
+                    )
+                    .await;
+                }
+            }
+            _ => store::remove_from_set(queue_id(&admin.id), "ids", submission.id).await?,
+        }
+    }
+    Ok(())
+}
+
+fn queue_id(table_id: &str) -> String {
+    format!("{}_queue#{}", KIND, table_id)
+}
+
+async fn queue(table_id: &str) -> Result<Vec<Submission>, Error> {
+    let queue: Option<SubmissionQueue> = store::get_record(queue_id(table_id)).await?;
+    let ids = queue
+        .and_then(|queue| queue.ids)
+        .map(|ids| ids.into_iter().collect())
+        .unwrap_or_default();
+    let mut submissions: Vec<Submission> = store::get_records(ids).await?;
+    submissions.sort_by_key(|submission| submission.submitted_at);
+    Ok(submissions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> SubmissionContent {
+        SubmissionContent::Text {
+            text: text.to_string(),
+        }
+    }
+
+    fn stroke(width: Option<f32>, points: Vec<[f32; 2]>) -> Stroke {
+        Stroke {
+            color: Some("#ff0000".to_string()),
+            width,
+            points,
+        }
+    }
+
+    fn png(width: u32, height: u32) -> SubmissionContent {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, width, height);
+            encoder.set_color(png::ColorType::Grayscale);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer
+                .write_image_data(&vec![0; (width * height) as usize])
+                .unwrap();
+        }
+        SubmissionContent::Png {
+            data: base64::encode(bytes),
+        }
+    }
+
+    #[test]
+    fn text_is_trimmed_and_limited() {
+        assert_eq!(
+            validate(text("  hi  "), &[]),
+            Ok(("text", "hi".to_string()))
+        );
+        assert_eq!(validate(text("   "), &[]), Err("empty"));
+        assert_eq!(validate(text(&"a".repeat(141)), &[]), Err("too_large"));
+    }
+
+    #[test]
+    fn text_goes_through_the_word_filter() {
+        let blocked = vec!["heck".to_string()];
+        assert_eq!(validate(text("oh h3ck"), &blocked), Err("blocked"));
+        assert!(validate(text("checked"), &blocked).is_ok());
+    }
+
+    #[test]
+    fn strokes_need_finite_points() {
+        let valid = vec![stroke(Some(2.0), vec![[0.0, 0.0], [1.0, 1.0]])];
+        assert_eq!(
+            validate(SubmissionContent::Strokes { strokes: valid }, &[])
+                .unwrap()
+                .0,
+            "strokes"
+        );
+
+        let empty = vec![stroke(None, Vec::new())];
+        assert_eq!(
+            validate(SubmissionContent::Strokes { strokes: empty }, &[]),
+            Err("empty")
+        );
+
+        let nan = vec![stroke(None, vec![[f32::NAN, 0.0]])];
+        assert_eq!(
+            validate(SubmissionContent::Strokes { strokes: nan }, &[]),
+            Err("invalid")
+        );
+
+        let flat = vec![stroke(Some(0.0), vec![[0.0, 0.0]])];
+        assert_eq!(
+            validate(SubmissionContent::Strokes { strokes: flat }, &[]),
+            Err("invalid")
+        );
+
+        let long = vec![stroke(None, vec![[0.0, 0.0]; 2001])];
+        assert_eq!(
+            validate(SubmissionContent::Strokes { strokes: long }, &[]),
+            Err("too_large")
+        );
+    }
+
+    #[test]
+    fn png_must_decode_within_the_size_limit() {
+        assert_eq!(validate(png(16, 16), &[]).unwrap().0, "png");
+        assert_eq!(validate(png(300, 1), &[]), Err("too_large"));
+        assert_eq!(
+            validate(
+                SubmissionContent::Png {
+                    data: "not base64!".to_string()
+                },
+                &[]
+            ),
+            Err("invalid")
+        );
+        assert_eq!(
+            validate(
+                SubmissionContent::Png {
+                    data: base64::encode(b"not a png")
+                },
+                &[]
+            ),
+            Err("invalid")
+        );
+    }
+}
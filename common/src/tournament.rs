@@ -0,0 +1,267 @@
+use crate::bracket::{Bracket, Entrant, Format};
+use crate::config;
+use crate::connection_operations::*;
+use crate::error::Error;
+use crate::leaderboard;
+use crate::models::*;
+use crate::que;
+use crate::send;
+use crate::slots;
+use crate::store;
+use crate::time;
+use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequestContext;
+
+const KIND: &str = "tournament";
+const FINISHED_RETENTION: u64 = 24 * 60 * 60;
+
+fn tournament_id(table_id: &str) -> String {
+    format!("{}#{}", KIND, table_id)
+}
+
+pub async fn find(table_id: &str) -> Result<Option<Bracket>, Error> {
+    let record: Option<Tournament> = store::get_record(tournament_id(table_id)).await?;
+    match record {
+        Some(record) => Ok(Some(serde_json::from_str(&record.bracket)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn all() -> Result<Vec<(String, Bracket)>, Error> {
+    let mut brackets = Vec::new();
+    for record in store::scan_records::<Tournament>(KIND).await? {
+        brackets.push((record.table_id, serde_json::from_str(&record.bracket)?));
+    }
+    Ok(brackets)
+}
+
+pub async fn active(table_id: &str) -> Result<Option<Bracket>, Error> {
+    Ok(find(table_id)
+        .await?
+        .filter(|bracket| !bracket.is_finished()))
+}
+
+async fn save(table_id: &str, bracket: &Bracket) -> Result<(), Error> {
+    let expires_at = if bracket.is_finished() {
+        Some(time::now_seconds() + FINISHED_RETENTION)
+    } else {
+        None
+    };
+    store::put_record(Tournament {
+        id: tournament_id(table_id),
+        kind: KIND.to_string(),
+        table_id: table_id.to_string(),
+        bracket: serde_json::to_string(bracket)?,
+        expires_at,
+    })
+    .await
+}
+
+pub async fn start(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+    format: Format,
+) -> Result<(), Error> {
+    if active(&admin.id).await?.is_some() {
+        return Err("Tournament already running".into());
+    }
+
+    let entrants: Vec<Connection> = find_que(Role::PlayerPong)
+        .await?
+        .into_iter()
+        .filter(|player| player.ready_by.is_none() && player.tournament_id.is_none())
+        .collect();
+    let bracket = Bracket::new(
+        format,
+        entrants
+            .iter()
+            .map(|player| Entrant {
+                id: player.id.clone(),
+                nickname: player.nickname.clone(),
+            })
+            .collect(),
+    )?;
+
+    for entrant in &entrants {
+        set_tournament(entrant.id.clone(), Some(admin.id.clone())).await?;
+    }
+    for player in find_players(admin).await? {
+        slots::vacate(&player).await?;
+        put_into_que(player.id.clone(), Role::PlayerPong).await;
+        send::rotated(request_context.clone(), &player).await;
+    }
+
+    schedule(request_context, admin, bracket).await
+}
+
+pub async fn cancel(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+) -> Result<(), Error> {
+    if let Some(bracket) = active(&admin.id).await? {
+        for entrant in &bracket.entrants {
+            set_tournament(entrant.id.clone(), None).await?;
+        }
+        store::delete_record(tournament_id(&admin.id)).await?;
+        for player in find_players(admin).await? {
+            slots::vacate(&player).await?;
+            put_into_que(player.id.clone(), Role::PlayerPong).await;
+            send::rotated(request_context.clone(), &player).await;
+        }
+    }
+    Ok(())
+}
+
+pub async fn record_result(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+    mut bracket: Bracket,
+    winner: &Connection,
+    loser: &Connection,
+) -> Result<(), Error> {
+    bracket.record(&winner.id, &loser.id)?;
+    leaderboard::record(winner, loser).await?;
+    complete_match(request_context, admin, bracket, vec![winner, loser]).await
+}
+
+pub async fn withdraw(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    table_id: &str,
+    player_id: &str,
+) -> Result<(), Error> {
+    let mut bracket = match active(table_id).await? {
+        Some(bracket) => bracket,
+        None => return Ok(()),
+    };
+    let admin = find_connection_in_db(UnresolvedConnection {
+        id: table_id.to_string(),
+    })
+    .await?;
+
+    let current = bracket.current.filter(|&index| {
+        bracket.matches[index]
+            .players
+            .iter()
+            .any(|player| player.as_deref() == Some(player_id))
+    });
+    match current.and_then(|index| bracket.opponent(index, player_id)) {
+        Some(opponent) => {
+            bracket.record(&opponent, player_id)?;
+            bracket.withdraw(player_id);
+            let opponent = find_connection_in_db(UnresolvedConnection { id: opponent }).await?;
+            complete_match(request_context, &admin, bracket, vec![&opponent]).await
+        }
+        None => {
+            bracket.withdraw(player_id);
+            if bracket.current.is_some() {
+                save(table_id, &bracket).await?;
+                broadcast(request_context, &admin, &bracket).await
+            } else {
+                schedule(request_context, &admin, bracket).await
+            }
+        }
+    }
+}
+
+async fn complete_match(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+    bracket: Bracket,
+    players: Vec<&Connection>,
+) -> Result<(), Error> {
+    for player in players {
+        if bracket.is_eliminated(&player.id) {
+            set_tournament(player.id.clone(), None).await?;
+        }
+        slots::vacate(player).await?;
+        put_into_que(player.id.clone(), Role::PlayerPong).await;
+        send::rotated(request_context.clone(), player).await;
+    }
+    schedule(request_context, admin, bracket).await
+}
+
+async fn schedule(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+    mut bracket: Bracket,
+) -> Result<(), Error> {
+    loop {
+        if bracket.is_finished() {
+            return finish(request_context, admin, bracket).await;
+        }
+        let index = match bracket.next_match() {
+            Some(index) => index,
+            None => break,
+        };
+
+        let ids: Vec<String> = bracket.matches[index]
+            .players
+            .iter()
+            .flatten()
+            .cloned()
+            .collect();
+        let mut players = Vec::new();
+        for id in ids {
+            match find_connection_in_db(UnresolvedConnection { id: id.clone() }).await {
+                Ok(player) if player.tournament_id.as_ref() == Some(&admin.id) => {
+                    players.push(player)
+                }
+                _ => bracket.withdraw(&id),
+            }
+        }
+        if players.len() < 2 {
+            continue;
+        }
+
+        bracket.current = Some(index);
+        let timeout = config::ready_timeout();
+        let ready_by = time::now_seconds() + timeout;
+        for (slot, mut player) in players.into_iter().enumerate() {
+            let seat = Seat {
+                table_id: admin.id.clone(),
+                slot: slot as i64,
+            };
+            player.slot = Some(seat.slot);
+            player.table_id = Some(seat.table_id.clone());
+            player.ready_by = Some(ready_by);
+            slots::take(&seat, &player.id).await?;
+            await_ready(player.id.clone(), &seat, ready_by).await?;
+            send::your_turn(request_context.clone(), &player, timeout).await;
+        }
+        break;
+    }
+
+    save(&admin.id, &bracket).await?;
+    broadcast(request_context, admin, &bracket).await
+}
+
+async fn finish(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+    bracket: Bracket,
+) -> Result<(), Error> {
+    for entrant in &bracket.entrants {
+        set_tournament(entrant.id.clone(), None).await?;
+    }
+    save(&admin.id, &bracket).await?;
+    broadcast(request_context.clone(), admin, &bracket).await?;
+    que::fill_table(request_context, admin).await
+}
+
+async fn broadcast(
+    request_context: ApiGatewayWebsocketProxyRequestContext,
+    admin: &Connection,
+    bracket: &Bracket,
+) -> Result<(), Error> {
+    let mut recipients: Vec<String> = find_audience(admin, Audience::Observers)
+        .await?
+        .into_iter()
+        .chain(find_admins(Role::PlayerDisplay).await?)
+        .map(|connection| connection.id)
+        .collect();
+    recipients.push(admin.id.clone());
+
+    for recipient in recipients {
+        send::bracket(request_context.clone(), recipient, bracket).await;
+    }
+    Ok(())
+}
@@ -1,5 +1,11 @@
 use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequest;
-use common::{connection_operations, error::Error, models, send};
+use common::{
+    bans,
+    config::{self, IpPrivacy},
+    connection_operations,
+    error::Error,
+    identity, metadata, models, que, roles, time,
+};
 use lambda::{lambda, Context};
 use simple_logger::SimpleLogger;
 
@@ -7,6 +13,7 @@ use simple_logger::SimpleLogger;
 #[tokio::main]
 async fn main(e: ApiGatewayWebsocketProxyRequest, _: Context) -> Result<(), Error> {
     SimpleLogger::new().init().unwrap();
+    roles::init()?;
 
     let event = e
         .clone()
@@ -16,11 +23,51 @@ async fn main(e: ApiGatewayWebsocketProxyRequest, _: Context) -> Result<(), Erro
 
     match event.as_ref() {
         "CONNECT" => {
-            connection_operations::save_player(
-                e.request_context
+            let source_ip = metadata::source_ip(e.request_context.identity.source_ip.clone());
+            let device = e.query_string_parameters.get("device").cloned();
+            let player_id = e
+                .query_string_parameters
+                .get("token")
+                .and_then(|token| identity::verify(token));
+            if bans::is_banned(&source_ip, &device, &player_id).await? {
+                return Err("Banned".into());
+            }
+            let max_connections = config::max_connections_per_ip();
+            match &source_ip {
+                Some(ip) if max_connections > 0 => {
+                    if connection_operations::count_connections_by_ip(ip.clone()).await?
+                        >= max_connections
+                    {
+                        return Err("Too many connections".into());
+                    }
+                }
+                None if max_connections > 0 && config::ip_privacy() == IpPrivacy::Drop => {
+                    log::warn!("maxConnectionsPerIp is ignored because ipPrivacy drops source ips");
+                }
+                _ => {}
+            }
+
+            let room = match e.query_string_parameters.get("code") {
+                Some(code) => {
+                    let code = code.to_uppercase();
+                    connection_operations::find_room_admin(code.clone()).await?;
+                    Some(code)
+                }
+                None => None,
+            };
+            connection_operations::save_player(models::Connection {
+                id: e
+                    .request_context
                     .connection_id
                     .ok_or("Missing connection id")?,
-            )
+                room,
+                source_ip,
+                user_agent: e.request_context.identity.user_agent.clone(),
+                device,
+                connected_at: Some(time::now_millis()),
+                player_id,
+                ..models::Connection::default()
+            })
             .await;
         }
         "DISCONNECT" => {
@@ -32,34 +79,7 @@ async fn main(e: ApiGatewayWebsocketProxyRequest, _: Context) -> Result<(), Erro
             let unresolved_connection = models::UnresolvedConnection { id: connection_id };
             let connection =
                 connection_operations::find_connection_in_db(unresolved_connection.clone()).await?;
-            if !connection.que {
-                match connection.role {
-                    Some(models::Role::PlayerPong) | Some(models::Role::PlayerDisplay) => {
-                        let admin =
-                            connection_operations::find_admin(connection.role.unwrap()).await?;
-                        send::inform_server(
-                            e.request_context.clone(),
-                            connection.id.clone(),
-                            admin.id.clone(),
-                            "DISCONNECTED".to_string(),
-                        )
-                        .await;
-                        if let Ok(player) =
-                            connection_operations::find_next_in_que(connection.role.unwrap()).await
-                        {
-                            send::inform_server(
-                                e.request_context.clone(),
-                                player.id.clone(),
-                                admin.id,
-                                "CONNECTED".to_string(),
-                            )
-                            .await;
-                            connection_operations::mark_player_active(player.id).await;
-                        }
-                    }
-                    _ => {}
-                }
-            }
+            que::release_slot(e.request_context.clone(), &connection, "DISCONNECTED").await?;
             connection_operations::delete_player(unresolved_connection.id).await;
         }
         _ => {
@@ -1,6 +1,6 @@
 //FROM SERVER TO CLIENTS
 use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequest;
-use common::{connection_operations, error::Error, models, send};
+use common::{connection_operations, error::Error, models, permissions, poll, roles, send};
 use lambda::{lambda, Context};
 use serde::{Deserialize, Serialize};
 use simple_logger::SimpleLogger;
@@ -8,12 +8,23 @@ use simple_logger::SimpleLogger;
 #[derive(Debug, Serialize, Deserialize)]
 struct AdminMessage {
     connection_id: Option<String>,
+    connection_ids: Option<Vec<String>>,
+    audience: Option<models::Audience>,
+    poll: Option<PollMessage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PollMessage {
+    question: String,
+    options: Vec<String>,
+    duration: u64,
 }
 
 #[lambda]
 #[tokio::main]
 async fn main(e: ApiGatewayWebsocketProxyRequest, _: Context) -> Result<(), Error> {
     SimpleLogger::new().init().unwrap();
+    roles::init()?;
 
     let connection_id = e
         .clone()
@@ -26,19 +37,84 @@ async fn main(e: ApiGatewayWebsocketProxyRequest, _: Context) -> Result<(), Erro
     let admin = connection_operations::find_connection_in_db(unresolved_connection).await?;
     let message_content: AdminMessage = serde_json::from_str(&message)?;
 
-    match admin.role {
-        Some(models::Role::AdminPong) | Some(models::Role::AdminDisplay) => {
-            if let Some(connection_id) = message_content.connection_id {
-                send::send(e.request_context.to_owned(), connection_id, message.clone()).await;
-            } else {
-                let players = connection_operations::find_players(admin.role.unwrap()).await?;
-                for player in players {
-                    send::send(e.request_context.to_owned(), player.id, message.clone()).await;
-                }
-            }
-        }
-        _ => {}
+    let message_type = Some(if message_content.poll.is_some() {
+        "poll"
+    } else if message_content.connection_id.is_some() || message_content.connection_ids.is_some() {
+        "direct"
+    } else if message_content.audience.is_some() {
+        "audience"
+    } else {
+        "players"
+    });
+    if !permissions::check(
+        e.request_context.clone(),
+        &admin,
+        "downstream",
+        message_type,
+    )
+    .await
+    {
+        return Ok(());
+    }
+
+    let candidates = if let Some(connection_id) = message_content.connection_id {
+        find_recipients(vec![connection_id]).await
+    } else if let Some(connection_ids) = message_content.connection_ids {
+        find_recipients(connection_ids).await
+    } else if let Some(audience) = message_content.audience.or_else(|| {
+        message_content
+            .poll
+            .as_ref()
+            .map(|_| models::Audience::Observers)
+    }) {
+        connection_operations::find_audience(&admin, audience).await?
+    } else {
+        connection_operations::find_players(&admin).await?
     };
 
+    let mut recipients = Vec::new();
+    for candidate in candidates {
+        if permissions::check_recipient(
+            e.request_context.clone(),
+            &admin,
+            "downstream",
+            message_type,
+            &candidate,
+        )
+        .await
+        {
+            recipients.push(candidate.id);
+        }
+    }
+
+    if let Some(poll) = message_content.poll {
+        poll::start(
+            e.request_context,
+            &admin,
+            poll.question,
+            poll.options,
+            poll.duration,
+            recipients,
+        )
+        .await?;
+        return Ok(());
+    }
+    for recipient in recipients {
+        send::send(e.request_context.to_owned(), recipient, message.clone()).await;
+    }
+
     Ok(())
 }
+
+async fn find_recipients(ids: Vec<String>) -> Vec<models::Connection> {
+    let mut recipients = Vec::new();
+    for id in ids {
+        let unresolved_connection = models::UnresolvedConnection { id };
+        if let Ok(connection) =
+            connection_operations::find_connection_in_db(unresolved_connection).await
+        {
+            recipients.push(connection);
+        }
+    }
+    recipients
+}
@@ -0,0 +1,83 @@
+//SCHEDULED HOUSEKEEPING
+use common::{
+    config, crowd, error::Error, poll, que, quiz, reactions, roles, send, snapshots, time,
+};
+use lambda::{lambda, Context};
+use serde_json::Value;
+use simple_logger::SimpleLogger;
+use std::time::Duration;
+
+// Each run keeps ticking until shortly before the next scheduled invocation, so
+// deadlines shorter than the schedule period are still kept to within one tick.
+const RUN_FOR: u64 = 55_000;
+
+#[lambda]
+#[tokio::main]
+async fn main(_: Value, _: Context) -> Result<(), Error> {
+    SimpleLogger::new().init().unwrap();
+    roles::init()?;
+
+    let request_context = send::context_from_env();
+    poll::close_due(request_context.clone()).await?;
+    snapshots::capture_due().await?;
+
+    let started = time::now_millis();
+    loop {
+        let tick_started = time::now_millis();
+        que::skip_expired(request_context.clone()).await?;
+        quiz::close_due(request_context.clone()).await?;
+        crowd::flush_due(request_context.clone()).await?;
+        reactions::flush_due(request_context.clone()).await?;
+
+        match next_tick(
+            started,
+            tick_started,
+            time::now_millis(),
+            config::scheduler_tick(),
+        ) {
+            Some(wait) => tokio::time::delay_for(Duration::from_millis(wait)).await,
+            None => break,
+        }
+    }
+
+    Ok(())
+}
+
+fn next_tick(started: u64, tick_started: u64, now: u64, tick: u64) -> Option<u64> {
+    let next = tick_started + tick.max(1);
+    if next >= started + RUN_FOR {
+        return None;
+    }
+    Some(next.saturating_sub(now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_until_the_next_invocation() {
+        assert_eq!(next_tick(0, 0, 200, 1000), Some(800));
+        assert_eq!(next_tick(0, 53_000, 53_100, 1000), Some(900));
+        assert_eq!(next_tick(0, 54_000, 54_100, 1000), None);
+    }
+
+    #[test]
+    fn slow_ticks_run_again_immediately() {
+        assert_eq!(next_tick(0, 1000, 2500, 1000), Some(0));
+    }
+
+    #[test]
+    fn ready_checks_expire_within_a_tick() {
+        let ready_timeout = 15_000;
+        let ticks: Vec<u64> = (0..)
+            .scan(0, |at, _| {
+                let tick = *at;
+                *at = tick + next_tick(0, tick, tick, 1000)?;
+                Some(tick)
+            })
+            .collect();
+        let expired_at = ticks.iter().find(|tick| **tick > ready_timeout).unwrap();
+        assert!(expired_at - ready_timeout <= 1000);
+    }
+}
@@ -1,5 +1,8 @@
 use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequest;
-use common::{connection_operations, error::Error, models, send};
+use common::{
+    bans, config, connection_operations, cooldown, error::Error, filter, game, identity, join,
+    models, nickname, permissions, que, rate_limit, roles, send, slots, time,
+};
 use lambda::{lambda, Context};
 use serde::{Deserialize, Serialize};
 use serde_json;
@@ -10,82 +13,238 @@ use std::string::ToString;
 struct SelectionMessage {
     role: models::Role,
     password: Option<String>,
+    nickname: Option<String>,
+    token: Option<String>,
 }
 
 #[lambda]
 #[tokio::main]
 async fn main(e: ApiGatewayWebsocketProxyRequest, _: Context) -> Result<(), Error> {
     SimpleLogger::new().init().unwrap();
+    roles::init()?;
 
+    let connection = current_connection(&e).await?;
+    let size = e.body.as_ref().map(|body| body.len()).unwrap_or(0);
+    if !rate_limit::check(e.request_context.clone(), &connection, size).await? {
+        return Ok(());
+    }
+
+    let route = e.request_context.route_key.clone().unwrap_or_default();
+    let route = match route.as_ref() {
+        "leave_queue" | "release_role" | "switch_role" | "ready" => route,
+        _ => "selection".to_string(),
+    };
+    if !permissions::check(e.request_context.clone(), &connection, &route, None).await {
+        return Ok(());
+    }
+
+    match route.as_ref() {
+        "leave_queue" => leave_queue(e).await,
+        "release_role" => release_role(e).await,
+        "switch_role" => select_role(e).await,
+        "ready" => ready(e).await,
+        _ => select_role(e).await,
+    }
+}
+
+async fn select_role(e: ApiGatewayWebsocketProxyRequest) -> Result<(), Error> {
     let message = e.body.clone().unwrap();
     let message_content: SelectionMessage = serde_json::from_str(&message)?;
 
+    let connection = current_connection(&e).await?;
+    let connection = identify(&e, connection, &message_content.token).await?;
+
+    let nickname = match &message_content.nickname {
+        Some(nickname) => match nickname::validate(nickname, &filter::words()) {
+            Ok(nickname) => Some(nickname),
+            Err(reason) => {
+                send::invalid_nickname(e.request_context.clone(), reason).await;
+                return Ok(());
+            }
+        },
+        None if connection.nickname.is_none() => Some(nickname::generate()),
+        None => None,
+    };
+    if let Some(nickname) = nickname {
+        connection_operations::set_nickname(connection.id.clone(), nickname).await?;
+    }
+
+    if connection.role == Some(message_content.role) {
+        return Ok(());
+    }
+
     match message_content.role {
-        models::Role::AdminDisplay | models::Role::AdminPong => {
+        role if role.is_privileged() => {
             if message_content.password.unwrap_or_else(|| "_".to_owned())
                 == "FikinkoPoznaSvojePrava321"
             {
                 let m = SelectionMessage {
                     role: message_content.role,
                     password: None,
+                    nickname: None,
+                    token: None,
                 };
-                save_role(m, e).await;
+                release(&e, &connection).await?;
+                save_role(m, e).await?;
             } else {
                 return Err("Wrong admin password".into());
             }
         }
         _ => {
-            save_role(message_content, e).await;
+            if let Some(remaining) = cooldown::remaining(&connection, message_content.role).await? {
+                send::cooldown(e.request_context, message_content.role, remaining).await;
+                return Ok(());
+            }
+            release(&e, &connection).await?;
+            save_role(message_content, e).await?;
         }
     }
 
     Ok(())
 }
 
+async fn leave_queue(e: ApiGatewayWebsocketProxyRequest) -> Result<(), Error> {
+    let connection = current_connection(&e).await?;
+    if connection.que {
+        connection_operations::reset_to_observer(connection.id.clone()).await;
+        send::role_released(
+            e.request_context.clone(),
+            models::Role::Observer,
+            "left_que",
+        )
+        .await;
+        que::release_slot(e.request_context, &connection, "LEFT").await?;
+    }
+    Ok(())
+}
+
+async fn release_role(e: ApiGatewayWebsocketProxyRequest) -> Result<(), Error> {
+    let connection = current_connection(&e).await?;
+    let released = release(&e, &connection).await;
+    send::role_released(e.request_context, models::Role::Observer, "released").await;
+    released
+}
+
+async fn ready(e: ApiGatewayWebsocketProxyRequest) -> Result<(), Error> {
+    let connection = current_connection(&e).await?;
+    match connection.ready_by {
+        Some(ready_by) if ready_by >= time::now_seconds() => {
+            que::activate(e.request_context, connection).await
+        }
+        Some(_) => que::skip(e.request_context, &connection).await,
+        None => {
+            send::ready_rejected(e.request_context, "no_ready_check").await;
+            Ok(())
+        }
+    }
+}
+
+async fn identify(
+    e: &ApiGatewayWebsocketProxyRequest,
+    mut connection: models::Connection,
+    token: &Option<String>,
+) -> Result<models::Connection, Error> {
+    match token.as_deref().and_then(identity::verify) {
+        Some(player_id) => {
+            if bans::is_banned(&None, &None, &Some(player_id.clone())).await? {
+                send::disconnect(e.request_context.clone(), connection.id.clone()).await;
+                return Err("Banned".into());
+            }
+            if connection.player_id.as_ref() != Some(&player_id) {
+                connection_operations::set_player_id(connection.id.clone(), player_id.clone())
+                    .await?;
+                connection.player_id = Some(player_id);
+            }
+        }
+        None if connection.player_id.is_none() => {
+            if let Some((player_id, token)) = identity::issue() {
+                connection_operations::set_player_id(connection.id.clone(), player_id.clone())
+                    .await?;
+                connection.player_id = Some(player_id.clone());
+                send::identity(e.request_context.clone(), player_id, token).await;
+            }
+        }
+        None => {}
+    }
+    Ok(connection)
+}
+
+async fn current_connection(
+    e: &ApiGatewayWebsocketProxyRequest,
+) -> Result<models::Connection, Error> {
+    let connection_id = e
+        .request_context
+        .connection_id
+        .clone()
+        .ok_or("Missing Connection ID")?;
+    connection_operations::find_connection_in_db(models::UnresolvedConnection { id: connection_id })
+        .await
+}
+
+async fn release(
+    e: &ApiGatewayWebsocketProxyRequest,
+    connection: &models::Connection,
+) -> Result<(), Error> {
+    match connection.role {
+        None | Some(models::Role::Observer) => Ok(()),
+        _ => {
+            connection_operations::reset_to_observer(connection.id.clone()).await;
+            que::release_slot(e.request_context.clone(), connection, "RELEASED").await
+        }
+    }
+}
+
 async fn save_role(
     message_content: SelectionMessage,
     event: ApiGatewayWebsocketProxyRequest,
 ) -> Result<(), Error> {
+    let mode = match game::for_role(message_content.role) {
+        Some(mode) => mode,
+        None if message_content.role != models::Role::Observer => {
+            set_role(message_content, event, None).await?;
+            return Ok(());
+        }
+        None => return Ok(()),
+    };
     let n_existing = connection_operations::get_player_count_by_role(message_content.role).await?;
 
-    match message_content.role {
-        models::Role::AdminDisplay | models::Role::AdminPong => {
-            if n_existing == 0 {
-                set_role(message_content, event).await;
-            }
-            Ok(())
+    if message_content.role == mode.admin_role() {
+        if n_existing == 0 || mode.multiple_tables() {
+            let admin = set_role(message_content, event.clone(), None).await?;
+            open_room(event.clone(), admin.clone()).await?;
+            mode.on_open(event.request_context.clone(), &admin).await?;
+            que::fill_table(event.request_context, &admin).await?;
         }
-        models::Role::PlayerDisplay => {
-            if n_existing > 0 {
-                put_into_que(message_content, event, n_existing).await;
-                Ok(())
-            } else {
-                set_role(message_content, event).await;
-                Ok(())
-            }
+    } else if connection_operations::find_admins(message_content.role)
+        .await?
+        .is_empty()
+    {
+        // Without an open table the role is held as before, and the first admin seats it.
+        if n_existing < mode.capacity() {
+            set_role(message_content, event, None).await?;
+        } else {
+            put_into_que(mode, message_content, event, n_existing).await;
         }
-        models::Role::PlayerPong => {
-            if n_existing > 1 {
-                put_into_que(message_content, event, n_existing).await;
-                Ok(())
-            } else {
-                let admin = connection_operations::find_admin(models::Role::PlayerPong).await?;
-                let connection = set_role(message_content, event.clone()).await;
-                send::inform_server(
-                    event.request_context,
-                    connection.id,
-                    admin.id,
-                    "CONNECTED".to_string(),
-                )
-                .await;
-                Ok(())
+    } else {
+        let connection_id = event
+            .request_context
+            .connection_id
+            .clone()
+            .unwrap_or_default();
+        match slots::claim_free(message_content.role, &connection_id).await? {
+            Some(seat) => {
+                set_role(message_content, event, Some(seat)).await?;
+            }
+            None => {
+                put_into_que(mode, message_content, event, n_existing).await;
             }
         }
-        _ => Ok(()),
     }
+    Ok(())
 }
 
 async fn put_into_que(
+    mode: &dyn game::GameMode,
     message_content: SelectionMessage,
     event: ApiGatewayWebsocketProxyRequest,
     n_existing: i64,
@@ -97,11 +256,8 @@ async fn put_into_que(
         .unwrap_or_default();
     connection_operations::put_into_que(connection_id, message_content.role).await;
 
-    match message_content.role {
-        models::Role::PlayerDisplay => {
-            connection_operations::time_out_first_in_que(message_content.role).await;
-        }
-        _ => {}
+    if mode.queue_policy() == game::QueuePolicy::Takeover {
+        connection_operations::time_out_first_in_que(message_content.role).await;
     }
     send::put_in_que(event.request_context, message_content.role, n_existing).await;
 }
@@ -109,7 +265,8 @@ async fn put_into_que(
 async fn set_role(
     message_conent: SelectionMessage,
     event: ApiGatewayWebsocketProxyRequest,
-) -> models::Connection {
+    seat: Option<models::Seat>,
+) -> Result<models::Connection, Error> {
     let role = message_conent.role;
     let connection_id = event
         .clone()
@@ -117,26 +274,29 @@ async fn set_role(
         .connection_id
         .unwrap_or_default();
 
-    let connection = models::Connection {
-        id: connection_id,
-        role: Some(message_conent.role),
-        que: false,
-    };
+    let con = connection_operations::update_role(connection_id, role, false, seat).await?;
 
-    let res = connection_operations::save_connection(connection.clone()).await;
-
-    if let Ok(con) = res {
-        send::role_accepted(event.request_context.clone(), role).await;
-        if let Ok(admin) = connection_operations::find_admin(message_conent.role).await {
-            send::inform_server(
-                event.request_context,
-                con.clone().id,
-                admin.id,
-                "CONNECTED".to_string(),
-            )
-            .await;
-        }
+    send::role_accepted(event.request_context.clone(), &con).await;
+    if let Ok(admin) = connection_operations::find_table_admin(&con).await {
+        send::inform_server(
+            event.request_context,
+            &con,
+            admin.id,
+            "CONNECTED".to_string(),
+        )
+        .await;
     }
 
-    connection
+    Ok(con)
+}
+
+async fn open_room(
+    event: ApiGatewayWebsocketProxyRequest,
+    admin: models::Connection,
+) -> Result<(), Error> {
+    let code = connection_operations::allocate_room(admin.id).await?;
+    let url = join::join_url(&code);
+    let qr = join::qr_code(&url, config::qr_format())?;
+    send::join_code(event.request_context, code, url, qr).await;
+    Ok(())
 }
@@ -1,5 +1,4 @@
-use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequestContext;
-use common::{connection_operations::*, error::Error, models::*, send::*};
+use common::{cooldown, error::Error, models::*, que::promote_next, roles, send, slots};
 use futures::future::try_join_all;
 use lambda::{lambda, Context};
 use serde::{Deserialize, Serialize};
@@ -9,7 +8,6 @@ use std::collections::HashMap;
 #[derive(Deserialize, Serialize, Clone)]
 struct DynamoDBEvent {
     records: Vec<DynamoDBEventRecord>,
-    request_context: ApiGatewayWebsocketProxyRequestContext,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -19,6 +17,9 @@ struct DynamoDBEventRecord {
     #[serde(default)]
     #[serde(rename = "eventName")]
     event_name: String,
+    #[serde(default)]
+    #[serde(rename = "userIdentity")]
+    user_identity: Option<DynamoDBUserIdentity>,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -29,50 +30,67 @@ struct DynamoDBStreamRecord {
     old_image: HashMap<String, String>,
 }
 
+#[derive(Deserialize, Serialize, Clone)]
+struct DynamoDBUserIdentity {
+    #[serde(rename = "principalId")]
+    principal_id: String,
+}
+
+impl DynamoDBEventRecord {
+    fn expired(&self) -> bool {
+        self.event_name == "REMOVE"
+            && self
+                .user_identity
+                .as_ref()
+                .map(|identity| identity.principal_id == "dynamodb.amazonaws.com")
+                .unwrap_or(false)
+    }
+}
+
 #[lambda]
 #[tokio::main]
 async fn main(e: DynamoDBEvent, _: Context) -> Result<(), Error> {
     SimpleLogger::new().init().unwrap();
+    roles::init()?;
 
-    let records = e.clone().records;
-    let roles = records
+    let records = e.records;
+    let freed_slots = records
         .iter()
-        .map(|record| {
-            if record.event_name == "REMOVE" {
-                let role = record
-                    .dynamodb
-                    .old_image
-                    .get("role")
-                    .map(|role| role.parse::<Role>().unwrap())
-                    .unwrap();
-                Some(role)
-            } else {
-                None
-            }
+        .filter(|record| record.expired())
+        .map(|record| -> Result<_, Error> {
+            let image = &record.dynamodb.old_image;
+            Ok(Connection {
+                id: image.get("id").ok_or("Missing id")?.to_owned(),
+                role: image.get("role").and_then(|role| role.parse::<Role>().ok()),
+                table_id: image.get("table_id").cloned(),
+                slot: image.get("slot").and_then(|slot| slot.parse::<i64>().ok()),
+                player_id: image.get("player_id").cloned(),
+                source_ip: image.get("source_ip").cloned(),
+                ..Connection::default()
+            })
         })
-        .filter(|role| role.is_some())
-        .map(|role| role.unwrap())
-        .map(|role| next_connection(role, e.clone()));
+        .collect::<Result<Vec<_>, Error>>()?
+        .into_iter()
+        .map(next_connection);
 
-    try_join_all(roles).await?;
+    try_join_all(freed_slots).await?;
 
     Ok(())
 }
 
-async fn next_connection(role: Role, event: DynamoDBEvent) -> Result<(), Error> {
-    if has_player(role).await {
-        if let Ok(admin) = find_admin(role).await {
-            if let Ok(player) = find_next_in_que(role).await {
-                inform_server(
-                    event.request_context,
-                    player.id.clone(),
-                    admin.id,
-                    "CONNECTED".to_string(),
-                )
-                .await;
-                mark_player_active(player.id).await;
-            }
+async fn next_connection(holder: Connection) -> Result<(), Error> {
+    let request_context = send::context_from_env();
+    // A role that was renamed or removed since the connection was stored still frees
+    // its seat, but there is no queue to promote from.
+    let role = match holder.role {
+        Some(role) => role,
+        None => {
+            log::warn!("Expired connection {} has an unknown role", holder.id);
+            return slots::vacate(&holder).await;
         }
-    }
+    };
+    cooldown::start(&holder, role).await?;
+    slots::vacate(&holder).await?;
+    promote_next(request_context, role, holder.seat()).await;
     Ok(())
 }
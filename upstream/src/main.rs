@@ -1,7 +1,10 @@
 //FROM CLIENT TO SERVER
 
 use aws_lambda_events::event::apigw::ApiGatewayWebsocketProxyRequest;
-use common::{connection_operations::*, error::Error, models, send};
+use common::{
+    connection_operations::*, crowd, error::Error, game, models, permissions, rate_limit, roles,
+    send,
+};
 use lambda::{lambda, Context};
 use simple_logger::SimpleLogger;
 
@@ -9,6 +12,7 @@ use simple_logger::SimpleLogger;
 #[tokio::main]
 async fn main(e: ApiGatewayWebsocketProxyRequest, _: Context) -> Result<(), Error> {
     SimpleLogger::new().init().unwrap();
+    roles::init()?;
 
     let message = e.body.clone().unwrap();
 
@@ -20,9 +24,41 @@ async fn main(e: ApiGatewayWebsocketProxyRequest, _: Context) -> Result<(), Erro
     let unresolved_connection = models::UnresolvedConnection { id: connection_id };
 
     let player = find_connection_in_db(unresolved_connection).await?;
-    let admin = find_admin(player.role.unwrap()).await?;
-    if player.id != admin.id {
-        send::send(e.request_context, admin.id, message);
+    if !rate_limit::check(e.request_context.clone(), &player, message.len()).await? {
+        return Ok(());
+    }
+    let message_type = permissions::upstream_type(&player);
+    if !permissions::check(
+        e.request_context.clone(),
+        &player,
+        "upstream",
+        Some(message_type),
+    )
+    .await
+    {
+        return Ok(());
+    }
+    if let None | Some(models::Role::Observer) = player.role {
+        return crowd::vote(e.request_context, &player, &message).await;
+    }
+    if let Some(mode) = player.role.and_then(game::for_role) {
+        if let Err(reason) = mode.validate(&message) {
+            send::message_rejected(e.request_context, reason).await;
+            return Ok(());
+        }
+    }
+    let admin = find_table_admin(&player).await?;
+    if player.id != admin.id
+        && permissions::check_recipient(
+            e.request_context.clone(),
+            &player,
+            "upstream",
+            Some(message_type),
+            &admin,
+        )
+        .await
+    {
+        send::forward(e.request_context, &player, admin.id, message).await;
     }
 
     Ok(())